@@ -0,0 +1,71 @@
+//! Throughput of the primality tests and the sieve, measured with criterion. The inputs are
+//! reproducible, so criterion's comparison against the last saved run is meaningful.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use fermats_kitchen::{esieve, number_theory::bench_inputs, primality::*, BigInt};
+
+const SEED: u64 = 116;
+
+fn bench_bigint_tests(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bigint");
+    for bits in [64, 256, 1024] {
+        let inputs = bench_inputs(100, bits, SEED);
+
+        group.bench_with_input(BenchmarkId::new("fermats_test", bits), &inputs, |b, inputs| {
+            b.iter(|| {
+                for n in inputs {
+                    black_box(fermats_test(black_box(n), BigInt::from(2)));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("miller_rabin_test", bits), &inputs, |b, inputs| {
+            b.iter(|| {
+                for n in inputs {
+                    black_box(miller_rabin_test(black_box(n), BigInt::from(2)));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_u64_tests(c: &mut Criterion) {
+    let inputs: Vec<u64> = bench_inputs(1000, 64, SEED)
+        .iter()
+        .map(|n| n.to_u64_wrapping())
+        .collect();
+
+    let mut group = c.benchmark_group("u64");
+    group.bench_function("is_prime_u64", |b| {
+        b.iter(|| {
+            for n in &inputs {
+                black_box(is_prime_u64(black_box(*n)));
+            }
+        })
+    });
+    group.bench_function("miller_rabin_test", |b| {
+        b.iter(|| {
+            for n in &inputs {
+                black_box(miller_rabin_test(black_box(n), 2));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_sieve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sieve");
+    // The largest bound takes long enough per run that criterion's default 100 samples would drag.
+    group.sample_size(10);
+    for upper_bound in [10_000, 1_000_000, 100_000_000] {
+        group.bench_with_input(BenchmarkId::from_parameter(upper_bound), &upper_bound, |b, n| {
+            b.iter(|| black_box(esieve::sieve(*n).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_bigint_tests, bench_u64_tests, bench_sieve);
+criterion_main!(benches);
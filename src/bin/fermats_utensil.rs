@@ -1,11 +1,36 @@
-use argh;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    process::ExitCode,
+    time::Instant,
+};
+
 use argh::FromArgs;
-use fermats_kitchen::BigInt;
-use rug::integer::SmallInteger;
+use fermats_kitchen::{
+    esieve,
+    factorization::factorize_checked,
+    format_in_base,
+    number_theory::euler_totient,
+    primality::{test_with_report, Primality},
+    BigInt,
+};
+use rug::ops::Pow;
 
 /// Utilities related to prime numbers
 #[derive(FromArgs, PartialEq, Debug)]
 struct Args {
+    #[argh(switch, description = "print how long the command took to stderr")]
+    time: bool,
+
+    #[argh(
+        option,
+        default = "10",
+        from_str_fn(parse_radix),
+        description = "the base, from 2 to 36, to write numbers in"
+    )]
+    radix: i32,
+
     #[argh(subcommand)]
     action: SubCommands,
 }
@@ -14,6 +39,11 @@ struct Args {
 #[argh(subcommand)]
 enum SubCommands {
     PTest(PTestCommand),
+    Carmichael(CarmichaelCommand),
+    Sieve(SieveCommand),
+    Batch(BatchCommand),
+    Totient(TotientCommand),
+    Factor(FactorCommand),
 }
 
 /// Run a primality test on a prime number. The result is reported as either composite,
@@ -21,19 +51,402 @@ enum SubCommands {
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(subcommand, name = "ptest")]
 struct PTestCommand {
-    #[argh(positional, description = "the number to test")]
-    number: BigInt,
+    #[argh(
+        positional,
+        from_str_fn(parse_number),
+        description = "the number to test, such as 97 or 2^61-1, if omitted numbers are read one \
+                       per line from stdin"
+    )]
+    number: Option<BigInt>,
+}
+
+/// Check whether a number is a Carmichael number, a composite that fools Fermat's test for every
+/// coprime base. Prints yes or no.
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "carmichael")]
+struct CarmichaelCommand {
+    #[argh(
+        positional,
+        from_str_fn(parse_number),
+        description = "the number to check, such as 97 or 2^61-1, if omitted numbers are read one \
+                       per line from stdin"
+    )]
+    number: Option<BigInt>,
 }
 
-fn main() {
+/// List all primes up to and including a bound, one per line.
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "sieve")]
+struct SieveCommand {
+    #[argh(positional, description = "the largest number to consider")]
+    upper_bound: usize,
+
+    #[argh(
+        option,
+        default = "4096",
+        description = "how many primes to write between each flush of stdout"
+    )]
+    chunk: usize,
+}
+
+/// Test every number in a file, one per line, and write the results to a CSV file with the columns
+/// number, result and method. Lines that aren't numbers are reported on stderr and skipped.
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "batch")]
+struct BatchCommand {
+    #[argh(option, description = "the file with the numbers to test")]
+    input: PathBuf,
+
+    #[argh(option, description = "the CSV file to write the results to")]
+    output: PathBuf,
+}
+
+/// Print Euler's totient of a number, the count of integers from 1 to the number that are coprime
+/// to it.
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "totient")]
+struct TotientCommand {
+    #[argh(
+        positional,
+        from_str_fn(parse_number),
+        description = "the number, at least 1, if omitted numbers are read one per line from stdin"
+    )]
+    number: Option<BigInt>,
+}
+
+/// Factor a number into primes, printed like 2^3 * 5 * 7^2.
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "factor")]
+struct FactorCommand {
+    #[argh(
+        positional,
+        from_str_fn(parse_number),
+        description = "the number to factor, if omitted numbers are read one per line from stdin"
+    )]
+    number: Option<BigInt>,
+}
+
+fn main() -> ExitCode {
     let args: Args = argh::from_env();
-    match args.action {
+    let start = Instant::now();
+
+    let exit_code = run(args.action, args.radix);
+
+    if args.time {
+        eprintln!("Elapsed: {:?}", start.elapsed());
+    }
+
+    exit_code
+}
+
+fn run(action: SubCommands, radix: i32) -> ExitCode {
+    match action {
         SubCommands::PTest(cmd) => {
-            if fermats_kitchen::primality::fermats_test(&cmd.number, SmallInteger::from(2).into()) {
-                println!("Probable prime")
+            for number in numbers(cmd.number) {
+                // Even numbers and the like are settled by trial division, without Fermat's test.
+                if fermats_kitchen::primality::quick_composite_check(&number) == Some(true) {
+                    println!("Composite")
+                } else if fermats_kitchen::primality::fermats_test(&number, BigInt::from(2)) {
+                    println!("Probable prime")
+                } else {
+                    println!("Composite")
+                }
+            }
+        }
+        SubCommands::Carmichael(cmd) => {
+            for number in numbers(cmd.number) {
+                if fermats_kitchen::primality::is_carmichael(&number) {
+                    println!("yes")
+                } else {
+                    println!("no")
+                }
+            }
+        }
+        SubCommands::Sieve(cmd) => {
+            let primes = match esieve::sieve(cmd.upper_bound) {
+                Ok(primes) => primes,
+                Err(e) => {
+                    eprintln!("Can't sieve: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            if let Err(e) = write_primes(io::stdout().lock(), &primes, cmd.chunk, radix) {
+                // A closed pipe just means the reader has seen enough.
+                if e.kind() != io::ErrorKind::BrokenPipe {
+                    eprintln!("Couldn't write the primes: {}", e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        SubCommands::Batch(cmd) => {
+            let input = match File::open(&cmd.input) {
+                Ok(input) => BufReader::new(input),
+                Err(e) => {
+                    eprintln!("Can't open {}: {}", cmd.input.display(), e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let output = match File::create(&cmd.output) {
+                Ok(output) => output,
+                Err(e) => {
+                    eprintln!("Can't create {}: {}", cmd.output.display(), e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            if let Err(e) = write_batch(output, parse_lines(input), radix) {
+                eprintln!("Couldn't write to {}: {}", cmd.output.display(), e);
+                return ExitCode::FAILURE;
+            }
+        }
+        SubCommands::Factor(cmd) => {
+            let mut exit_code = ExitCode::SUCCESS;
+            for number in numbers(cmd.number) {
+                if number < 1 {
+                    eprintln!("Only positive numbers can be factored, not {}", number);
+                    exit_code = ExitCode::FAILURE;
+                    continue;
+                }
+                match factorize_checked(&number) {
+                    Ok(factors) => println!("{}", format_factors(&factors, radix)),
+                    Err(partial) => {
+                        eprintln!(
+                            "Couldn't factor {}: {}, the factors found were {}",
+                            number,
+                            partial,
+                            format_factors(&partial.factors, radix)
+                        );
+                        exit_code = ExitCode::FAILURE;
+                    }
+                }
+            }
+            return exit_code;
+        }
+        SubCommands::Totient(cmd) => {
+            let mut exit_code = ExitCode::SUCCESS;
+            for number in numbers(cmd.number) {
+                // One bad number shouldn't cost the rest of stdin its answers.
+                if number < 1 {
+                    eprintln!("The totient is only defined for positive numbers, not {}", number);
+                    exit_code = ExitCode::FAILURE;
+                    continue;
+                }
+                println!("{}", format_in_base(&euler_totient(&number), radix));
+            }
+            return exit_code;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Writes the primes one per line in base `radix` through a buffer, flushing after every `chunk`
+/// primes.
+fn write_primes(out: impl Write, primes: &[usize], chunk: usize, radix: i32) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    for group in primes.chunks(chunk.max(1)) {
+        for p in group {
+            // Decimal is by far the common case, and needs no detour through BigInt.
+            if radix == 10 {
+                writeln!(out, "{}", p)?;
+            } else {
+                writeln!(out, "{}", format_in_base(&BigInt::from(*p), radix))?;
+            }
+        }
+        out.flush()?;
+    }
+    Ok(())
+}
+
+/// Writes a factorization like `2^3 * 5 * 7^2`, with the primes in base `radix`. The exponents stay
+/// in decimal, the way [parse_number] reads them. No factors at all is written `1`.
+fn format_factors(factors: &[(BigInt, u32)], radix: i32) -> String {
+    if factors.is_empty() {
+        return format_in_base(&BigInt::from(1), radix);
+    }
+    factors
+        .iter()
+        .map(|(p, e)| match e {
+            1 => format_in_base(p, radix),
+            _ => format!("{}^{}", format_in_base(p, radix), e),
+        })
+        .collect::<Vec<_>>()
+        .join(" * ")
+}
+
+/// Tests each number and writes a CSV row with the verdict and the test that settled it.
+fn write_batch(
+    out: impl Write,
+    numbers: impl Iterator<Item = BigInt>,
+    radix: i32,
+) -> io::Result<()> {
+    let mut out = BufWriter::new(out);
+    writeln!(out, "number,result,method")?;
+    for number in numbers {
+        let report = test_with_report(&number);
+        let result = match report.primality {
+            Primality::Composite => "composite",
+            Primality::ProbablyPrime => "probable prime",
+            Primality::Prime => "prime",
+        };
+        // Below 2 nothing is run, the number is composite by definition.
+        let method = report
+            .tests
+            .last()
+            .map_or("none", |(name, _)| name.as_str());
+        writeln!(
+            out,
+            "{},{},{}",
+            format_in_base(&number, radix),
+            result,
+            method
+        )?;
+    }
+    out.flush()
+}
+
+/// The number given on the command line, or else the numbers on stdin, one per line.
+fn numbers(number: Option<BigInt>) -> Box<dyn Iterator<Item = BigInt>> {
+    match number {
+        Some(number) => Box::new(std::iter::once(number)),
+        None => Box::new(parse_lines(io::stdin().lock())),
+    }
+}
+
+/// The numbers in `input`, one per line. Blank lines are skipped and lines that don't parse or
+/// aren't UTF-8 are reported on stderr. A read error ends the input.
+fn parse_lines(input: impl BufRead) -> impl Iterator<Item = BigInt> {
+    let lines = input.split(b'\n').map_while(|line| match line {
+        Ok(line) => Some(line),
+        Err(e) => {
+            eprintln!("Can't read the input: {}", e);
+            None
+        }
+    });
+    lines.enumerate().filter_map(|(index, line)| {
+        let line = match String::from_utf8(line) {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Line {}: {}", index + 1, e);
+                return None;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        match parse_number(line) {
+            Ok(number) => Some(number),
+            Err(e) => {
+                eprintln!("Line {}: {:?} is not a number: {}", index + 1, line, e);
+                None
+            }
+        }
+    })
+}
+
+fn parse_radix(text: &str) -> Result<i32, String> {
+    match text.parse() {
+        Ok(radix) if (2..=36).contains(&radix) => Ok(radix),
+        _ => Err(format!("{:?} is not a radix from 2 to 36", text)),
+    }
+}
+
+/// Parses an integer written as an expression like `2^61-1` or `3*10^18`. Supports `^`, `*`, `+`
+/// and `-` with the usual precedence, `^` being right associative. Whitespace is ignored.
+fn parse_number(text: &str) -> Result<BigInt, String> {
+    let mut expression = Expression {
+        chars: text.chars().filter(|c| !c.is_whitespace()).collect(),
+        position: 0,
+    };
+    let value = expression.sum()?;
+    match expression.peek() {
+        Some(c) => Err(format!("unexpected {:?}", c)),
+        None => Ok(value),
+    }
+}
+
+/// The most bits a power in an expression may have, 8 MiB worth. Anything larger would take ages to
+/// test anyway, and could exhaust the memory before then.
+const MAX_POWER_BITS: u64 = 1 << 26;
+
+/// A recursive descent parser for [parse_number], one method per precedence level.
+struct Expression {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Expression {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    /// Consumes `c` if it is next.
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn sum(&mut self) -> Result<BigInt, String> {
+        let mut value = self.product()?;
+        loop {
+            if self.eat('+') {
+                value += self.product()?;
+            } else if self.eat('-') {
+                value -= self.product()?;
             } else {
-                println!("Composite")
+                return Ok(value);
             }
         }
     }
+
+    fn product(&mut self) -> Result<BigInt, String> {
+        let mut value = self.negation()?;
+        while self.eat('*') {
+            value *= self.negation()?;
+        }
+        Ok(value)
+    }
+
+    /// A leading minus binds looser than `^`, so `-2^2` is `-4`.
+    fn negation(&mut self) -> Result<BigInt, String> {
+        if self.eat('-') {
+            Ok(-self.negation()?)
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<BigInt, String> {
+        let base = self.integer()?;
+        if !self.eat('^') {
+            return Ok(base);
+        }
+        let exponent = self.power()?;
+        let exponent = exponent
+            .to_u32()
+            .ok_or_else(|| format!("the exponent {} is too large", exponent))?;
+        // Checked up front, since the power is allocated in full before anything can look at it.
+        if u64::from(exponent) * u64::from(base.significant_bits()) > MAX_POWER_BITS {
+            return Err(format!("{}^{} has more than {} bits", base, exponent, MAX_POWER_BITS));
+        }
+        Ok(base.pow(exponent))
+    }
+
+    fn integer(&mut self) -> Result<BigInt, String> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(match self.peek() {
+                Some(c) => format!("expected a digit, found {:?}", c),
+                None => "expected a digit at the end".to_string(),
+            });
+        }
+        let digits: String = self.chars[start..self.position].iter().collect();
+        Ok(digits.parse().expect("a run of digits is an integer"))
+    }
 }
@@ -0,0 +1,1084 @@
+//! # Sieve of Eratosthenes
+//! Finds all the primes up to some upper bound by crossing out the multiples of each prime as it
+//! is found. Whatever survives the crossing out is prime.
+//!
+//! The sieve keeps one bit per integer up to the bound, so memory use grows linearly with the
+//! bound: sieving to `10**9` needs about 125 MB.
+//!
+//! # Example
+//!
+//! ```
+//! use fermats_kitchen::esieve::*;
+//!
+//! let mut state = SieveState::with_upper_bound(30).unwrap();
+//! state.run();
+//! assert_eq!(state.primes_found(), &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+//! ```
+
+mod bitset;
+
+#[cfg(test)]
+mod esieve_tests;
+
+use std::{fmt, sync::OnceLock};
+
+pub use bitset::FixedBitSet;
+
+use crate::{
+    primality::{fermats_test_checked, is_prime_u64},
+    BigInt,
+};
+
+/// Why a sieve couldn't be set up. Implements [std::error::Error], so it works with `?` in
+/// functions returning `Box<dyn std::error::Error>`.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The upper bound is below 2, so there is nothing to sieve.
+    BadBound(usize),
+    /// The bitset for the upper bound couldn't be allocated.
+    BadMemory {
+        /// The size of the bitset that was asked for.
+        requested_bytes: usize,
+        /// The most bytes a single allocation can ask for. Smaller requests can still fail when
+        /// the system is out of memory, so retrying with a smaller bound may help either way.
+        limit: usize,
+    },
+    /// The bytes given to [load_small_primes_from] aren't a table from [dump_small_primes].
+    BadTable,
+    /// The small prime table was already sieved or loaded, so it can't be loaded again.
+    AlreadyLoaded,
+    /// The residue class `a mod m` given to [primes_in_residue_class] has `m = 0` or
+    /// `gcd(a, m) > 1`, so it holds at most one prime.
+    BadResidueClass { a: usize, m: usize },
+}
+
+impl Error {
+    /// [Error::BadMemory] for a bitset of `bits` bits.
+    fn bad_memory(bits: usize) -> Self {
+        Error::bad_memory_bytes(bits.div_ceil(64).saturating_mul(8))
+    }
+
+    /// [Error::BadMemory] for `requested_bytes` bytes.
+    fn bad_memory_bytes(requested_bytes: usize) -> Self {
+        Error::BadMemory { requested_bytes, limit: MAX_ALLOCATION_BYTES }
+    }
+}
+
+/// Rust never allocates more than `isize::MAX` bytes at once.
+const MAX_ALLOCATION_BYTES: usize = isize::MAX as usize;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BadBound(bound) => {
+                write!(f, "the upper bound {} is below 2, there is nothing to sieve", bound)
+            }
+            Error::BadMemory { requested_bytes, limit } => write!(
+                f,
+                "couldn't allocate {} bytes for the sieve, the limit is {} bytes",
+                requested_bytes, limit
+            ),
+            Error::BadTable => write!(f, "the bytes aren't a small prime table"),
+            Error::AlreadyLoaded => write!(f, "the small prime table is already loaded"),
+            Error::BadResidueClass { a, m } => {
+                write!(f, "{} mod {} isn't a residue class coprime to the modulus", a, m)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The primes the largest wheel is built from. The smaller wheels use a prefix of these.
+const WHEEL_PRIMES: [usize; 4] = [2, 3, 5, 7];
+
+/// How many integers the sieve crosses out up front, as multiples of the first few primes, before
+/// it starts finding primes one at a time.
+///
+/// A bigger wheel saves more of the slow one-at-a-time crossing out, at the cost of a longer
+/// pattern to copy in. The primes found are the same whichever wheel is used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Wheel {
+    /// Cross out nothing up front.
+    None,
+    /// Cross out the multiples of 2 and 3.
+    Mod6,
+    /// Cross out the multiples of 2, 3 and 5.
+    Mod30,
+    /// Cross out the multiples of 2, 3, 5 and 7.
+    #[default]
+    Mod210,
+}
+
+impl Wheel {
+    /// The primes whose multiples this wheel crosses out.
+    fn primes(self) -> &'static [usize] {
+        match self {
+            Wheel::None => &[],
+            Wheel::Mod6 => &WHEEL_PRIMES[..2],
+            Wheel::Mod30 => &WHEEL_PRIMES[..3],
+            Wheel::Mod210 => &WHEEL_PRIMES,
+        }
+    }
+
+    /// The crossed out pattern as sieve blocks, to be repeated over the whole bitset.
+    fn blocks(self) -> &'static [u64] {
+        match self {
+            Wheel::None => &[0],
+            Wheel::Mod6 => &MOD6_BLOCKS,
+            Wheel::Mod30 => &MOD30_BLOCKS,
+            Wheel::Mod210 => &MOD210_BLOCKS,
+        }
+    }
+}
+
+/// The multiples of the first `prime_count` of [WHEEL_PRIMES] laid out as sieve blocks, where bit
+/// `i` stands for `i + 1`. `64 * N` must be a multiple of the primes' product, so that repeating
+/// the blocks covers a bitset of any size.
+const fn make_wheel_blocks<const N: usize>(prime_count: usize) -> [u64; N] {
+    let mut blocks = [0; N];
+    let mut bit = 0;
+    while bit < 64 * N {
+        let mut i = 0;
+        while i < prime_count {
+            if (bit + 1) % WHEEL_PRIMES[i] == 0 {
+                blocks[bit / 64] |= 1 << (bit % 64);
+            }
+            i += 1;
+        }
+        bit += 1;
+    }
+    blocks
+}
+
+/// `64 * 3` is a multiple of 6.
+const MOD6_BLOCKS: [u64; 3] = make_wheel_blocks(2);
+/// `64 * 15` is a multiple of 30.
+const MOD30_BLOCKS: [u64; 15] = make_wheel_blocks(3);
+/// `64 * 105` is a multiple of 210.
+const MOD210_BLOCKS: [u64; 105] = make_wheel_blocks(4);
+
+/// The state of a sieve that has been run fully, partially or not at all.
+pub struct SieveState {
+    upper_bound: usize,
+    /// Bit `i` is set when `i + 1` is known to be composite.
+    composites: FixedBitSet,
+    primes: Vec<usize>,
+    /// The largest prime found so far, or 1 before any has been found.
+    last_divisor: usize,
+    stats: SieveStats,
+    wheel: Wheel,
+}
+
+/// Counts of the work a [SieveState] has done so far, see [SieveState::stats].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SieveStats {
+    /// How many integers have been crossed out as multiples of a prime. The wheel primes' multiples
+    /// are crossed out in bulk when the sieve is set up and aren't counted.
+    pub marks_performed: u64,
+    /// How many integers have been checked as the possible next prime.
+    pub divisors_tested: u64,
+}
+
+impl SieveState {
+    /// Sets up a sieve for the primes `p <= upper_bound`, using the default [Wheel].
+    ///
+    /// # Errors
+    /// - [Error::BadBound] if `upper_bound < 2`.
+    /// - [Error::BadMemory] if the bitset can't be allocated, which is what happens for bounds
+    ///   close to `usize::MAX`.
+    pub fn with_upper_bound(upper_bound: usize) -> Result<Self> {
+        Self::with_upper_bound_and_wheel(upper_bound, Wheel::default())
+    }
+
+    /// Sets up a sieve for the primes `p <= upper_bound` that starts by crossing out the multiples
+    /// covered by `wheel`.
+    ///
+    /// # Errors
+    /// The same as [SieveState::with_upper_bound].
+    pub fn with_upper_bound_and_wheel(upper_bound: usize, wheel: Wheel) -> Result<Self> {
+        if upper_bound < 2 {
+            return Err(Error::BadBound(upper_bound));
+        }
+
+        let mut composites = FixedBitSet::try_with_capacity(upper_bound)
+            .map_err(|_| Error::bad_memory(upper_bound))?;
+
+        // Cross out the multiples of the wheel primes in bulk, but not the primes themselves.
+        for (block, pattern) in composites
+            .as_mut_slice()
+            .iter_mut()
+            .zip(wheel.blocks().iter().cycle())
+        {
+            *block = *pattern;
+        }
+        for p in wheel.primes().iter().filter(|p| **p <= upper_bound) {
+            composites.set(p - 1, false);
+        }
+
+        Ok(Self {
+            upper_bound,
+            composites,
+            primes: vec![],
+            last_divisor: 1,
+            stats: SieveStats::default(),
+            wheel,
+        })
+    }
+
+    pub fn upper_bound(&self) -> usize {
+        self.upper_bound
+    }
+
+    /// Runs the sieve until all primes up to the upper bound have been found.
+    pub fn run(&mut self) {
+        self.run_with_min_prime(0);
+    }
+
+    /// Like [SieveState::run], but only keeps the primes `p >= min_prime` in
+    /// [SieveState::primes_found]. The smaller primes still cross out their multiples, they just
+    /// aren't stored, which saves memory when only the large primes are of interest.
+    pub fn run_with_min_prime(&mut self, min_prime: usize) {
+        // Room for every prime still to be kept up front, so the list never grows while sieving.
+        // The primes below min_prime and those already in the list need none. Nothing is kept
+        // when min_prime is past the bound, as in prime_mask, so nothing is reserved then either.
+        if min_prime <= self.upper_bound {
+            let skipped =
+                prime_count_lower_bound(min_prime.saturating_sub(1)).max(self.primes.len());
+            let additional = prime_count_upper_bound(self.upper_bound).saturating_sub(skipped);
+            self.primes.reserve_exact(additional);
+        }
+        while let Some(p) = self.sieve_once() {
+            if p >= min_prime {
+                self.primes.push(p);
+            }
+        }
+    }
+
+    /// Finds the next prime, crosses out its multiples and adds it to [SieveState::primes_found].
+    /// Returns the prime, or `None` once the sieve is done.
+    ///
+    /// Calling this until it returns `None` does the same as [SieveState::run], but leaves room to
+    /// do other work or look at the state in between.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fermats_kitchen::esieve::*;
+    /// let mut state = SieveState::with_upper_bound(10).unwrap();
+    /// assert_eq!(state.step(), Ok(Some(2)));
+    /// assert_eq!(state.step(), Ok(Some(3)));
+    /// state.run();
+    /// assert_eq!(state.primes_found(), [2, 3, 5, 7]);
+    /// assert_eq!(state.step(), Ok(None));
+    /// ```
+    ///
+    /// # Errors
+    /// - [Error::BadMemory] if [SieveState::primes_found] can't grow to hold another prime. The
+    ///   sieve is left as it was, so the step can be retried.
+    pub fn step(&mut self) -> Result<Option<usize>> {
+        // Room first, so a failure doesn't lose a prime that has already been crossed out.
+        self.primes.try_reserve(1).map_err(|_| {
+            let len = self.primes.len().saturating_add(1);
+            Error::bad_memory_bytes(len.saturating_mul(std::mem::size_of::<usize>()))
+        })?;
+        let Some(p) = self.sieve_once() else {
+            return Ok(None);
+        };
+        self.primes.push(p);
+        Ok(Some(p))
+    }
+
+    /// The primes found so far, in increasing order.
+    pub fn primes_found(&self) -> &[usize] {
+        &self.primes
+    }
+
+    /// [SieveState::primes_found] converted to [BigInt], for feeding into the bignum algorithms.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fermats_kitchen::esieve::*;
+    /// let mut state = SieveState::with_upper_bound(10).unwrap();
+    /// state.run();
+    /// assert_eq!(state.primes_as_bigint(), [2, 3, 5, 7]);
+    /// ```
+    pub fn primes_as_bigint(&self) -> Vec<BigInt> {
+        self.primes.iter().map(|p| BigInt::from(*p)).collect()
+    }
+
+    /// Whether [SieveState::primes_found] is strictly increasing, as it should always be. Meant as
+    /// a check for tests and debugging.
+    pub fn is_sorted(&self) -> bool {
+        self.primes.windows(2).all(|pair| pair[0] < pair[1])
+    }
+
+    /// The `k`-th of [SieveState::primes_found], counting from 1, or `None` if fewer than `k`
+    /// primes have been found. After a full [SieveState::run] this is the `k`-th prime.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fermats_kitchen::esieve::*;
+    /// let mut state = SieveState::with_upper_bound(20).unwrap();
+    /// state.run();
+    /// assert_eq!(state.nth_prime_in_sieve(4), Some(7));
+    /// assert_eq!(state.nth_prime_in_sieve(9), None);
+    /// ```
+    pub fn nth_prime_in_sieve(&self, k: usize) -> Option<usize> {
+        self.primes.get(k.checked_sub(1)?).copied()
+    }
+
+    /// How far the sieve has got through `[1, upper_bound]`, from 0.0 to 1.0, for showing progress.
+    ///
+    /// The sieve checks each integer in turn, so this is the last prime found over the upper bound.
+    /// After a full [SieveState::run] it is the largest prime over the bound, just below 1.0. The
+    /// small primes cross out the most multiples, so more than this fraction of the time has
+    /// passed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fermats_kitchen::esieve::*;
+    /// let mut state = SieveState::with_upper_bound(100).unwrap();
+    /// state.run();
+    /// assert_eq!(state.progress(), 0.97);
+    /// ```
+    pub fn progress(&self) -> f64 {
+        self.last_divisor as f64 / self.upper_bound as f64
+    }
+
+    /// The work done so far, for studying how the sieve behaves.
+    pub fn stats(&self) -> SieveStats {
+        self.stats
+    }
+
+    /// Finds the next prime and crosses out its multiples, returning the prime. Returns `None` when
+    /// the sieve is done.
+    fn sieve_once(&mut self) -> Option<usize> {
+        let divisor = self.find_next_divisor()?;
+        // primes_found relies on the primes turning up in increasing order.
+        debug_assert!(divisor > self.last_divisor, "{} found after {}", divisor, self.last_divisor);
+        self.mark_multiples_as_composite(divisor);
+        self.last_divisor = divisor;
+        Some(divisor)
+    }
+
+    fn find_next_divisor(&mut self) -> Option<usize> {
+        let mut potential_divisor = self.last_divisor;
+        // The increment comes before the check, so starting from last_divisor = 1 the first
+        // integer checked is 2, and 1 is never taken for a prime. last_divisor never exceeds
+        // upper_bound and we only step while strictly below it, so the increment can't overflow
+        // even when upper_bound == usize::MAX.
+        while potential_divisor < self.upper_bound {
+            potential_divisor += 1;
+            self.stats.divisors_tested += 1;
+            if !self.composites.contains(potential_divisor - 1) {
+                return Some(potential_divisor);
+            }
+        }
+        None
+    }
+
+    fn mark_multiples_as_composite(&mut self, divisor: usize) {
+        // Every integer is a multiple of 1, crossing them out would leave nothing. The multiples of
+        // the wheel primes were crossed out when the sieve was set up.
+        if divisor < 2 || self.wheel.primes().contains(&divisor) {
+            return;
+        }
+
+        // Multiples below divisor**2 have a smaller prime factor and are already crossed out.
+        let Some(mut multiple) = divisor.checked_mul(divisor) else {
+            return;
+        };
+        while multiple <= self.upper_bound {
+            self.composites.insert(multiple - 1);
+            self.stats.marks_performed += 1;
+            multiple = match multiple.checked_add(divisor) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+}
+
+/// An upper bound on the number of primes `p <= n`, from the estimate `n / ln(n)`.
+///
+/// Rosser and Schoenfeld showed `pi(n) < 1.25506 n / ln(n)` for all `n > 1`, so this is never too
+/// small, and for large `n` it is only a little too big.
+fn prime_count_upper_bound(n: usize) -> usize {
+    if n < 2 {
+        return 0;
+    }
+    let n = n as f64;
+    (1.25506 * n / n.ln()).ceil() as usize
+}
+
+/// A lower bound on the number of primes `p <= n`, the counterpart of [prime_count_upper_bound].
+///
+/// Rosser and Schoenfeld also showed `pi(n) > n / ln(n)` for `n >= 17`. Below that this is 0.
+fn prime_count_lower_bound(n: usize) -> usize {
+    if n < 17 {
+        return 0;
+    }
+    let n = n as f64;
+    (n / n.ln()).floor() as usize
+}
+
+/// Returns all primes `p <= upper_bound` in increasing order.
+///
+/// # Errors
+/// The same as [SieveState::with_upper_bound].
+pub fn sieve(upper_bound: usize) -> Result<Vec<usize>> {
+    sieve_with_wheel(upper_bound, Wheel::default())
+}
+
+/// The first `count` primes, in increasing order.
+///
+/// The bound to sieve to comes from Rosser's theorem, that the `n`-th prime is below
+/// `n (ln(n) + ln(ln(n)))` for `n >= 6`. Should the sieve still come up short, the bound is doubled
+/// until it doesn't.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(sieve_for_count(5).unwrap(), [2, 3, 5, 7, 11]);
+/// ```
+///
+/// # Errors
+/// - [Error::BadMemory] if the bound for `count` primes is too large to sieve to.
+pub fn sieve_for_count(count: usize) -> Result<Vec<usize>> {
+    if count == 0 {
+        return Ok(vec![]);
+    }
+
+    let mut bound = nth_prime_upper_bound(count);
+    loop {
+        let mut primes = sieve(bound)?;
+        if primes.len() >= count {
+            primes.truncate(count);
+            return Ok(primes);
+        }
+        bound = bound.checked_mul(2).ok_or(Error::bad_memory(usize::MAX))?;
+    }
+}
+
+/// An upper bound on the `n`-th prime, see [sieve_for_count].
+fn nth_prime_upper_bound(n: usize) -> usize {
+    // The fifth prime is 11, and the formula needs n >= 6.
+    if n < 6 {
+        return 11;
+    }
+    let n = n as f64;
+    (n * (n.ln() + n.ln().ln())).ceil() as usize
+}
+
+/// [sieve] with a chosen [Wheel]. The primes are the same for every wheel, only the time taken
+/// differs.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(sieve_with_wheel(30, Wheel::Mod6).unwrap(), sieve(30).unwrap());
+/// ```
+///
+/// # Errors
+/// The same as [SieveState::with_upper_bound].
+pub fn sieve_with_wheel(upper_bound: usize, wheel: Wheel) -> Result<Vec<usize>> {
+    let mut state = SieveState::with_upper_bound_and_wheel(upper_bound, wheel)?;
+    state.run();
+    Ok(state.primes)
+}
+
+/// The segment length [optimal_segment_size] suggests: 32 KB of bits, the size of a typical L1
+/// data cache.
+const SEGMENT_LEN: usize = 32 * 1024 * 8;
+
+/// A segment length for [segmented_sieve], in integers per segment.
+///
+/// Each segment's bitset is crossed out by every small prime in turn, so it is fastest when the
+/// bitset stays in cache. This is 32 KB worth of bits, which fits the L1 data cache of most CPUs.
+/// It isn't measured on the machine at hand.
+pub fn optimal_segment_size() -> usize {
+    SEGMENT_LEN
+}
+
+/// Returns all primes `p <= upper_bound` in increasing order, like [sieve] but with a `u64` bound.
+///
+/// On 32-bit targets `usize` stops at `2**32 - 1`, so [SieveState] can't hold a larger bound. This
+/// sieves in segments of a fixed size instead, crossing out the multiples of the primes up to
+/// `sqrt(upper_bound)` in one segment at a time, so the bitset always fits. The returned primes
+/// still take memory in proportion to their count, which is what limits the bound in practice.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(sieve_u64(30).unwrap(), [2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+/// ```
+///
+/// # Errors
+/// - [Error::BadBound] if `upper_bound < 2`.
+/// - [Error::BadMemory] if the bitset for the segments can't be allocated.
+pub fn sieve_u64(upper_bound: u64) -> Result<Vec<u64>> {
+    segmented_sieve(upper_bound, 0)
+}
+
+/// [sieve_u64] with segments of `segment_len` integers, or of [optimal_segment_size] when
+/// `segment_len` is 0. The primes are the same for every segment length, only the time taken
+/// differs.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(segmented_sieve(1000, 64).unwrap(), sieve_u64(1000).unwrap());
+/// ```
+///
+/// # Errors
+/// The same as [sieve_u64].
+pub fn segmented_sieve(upper_bound: u64, segment_len: usize) -> Result<Vec<u64>> {
+    if upper_bound < 2 {
+        return Err(Error::BadBound(upper_bound as usize));
+    }
+    let segment_len = if segment_len == 0 {
+        optimal_segment_size()
+    } else {
+        segment_len
+    };
+
+    // sqrt(u64::MAX) < 2**32, so this fits in usize even on 32-bit targets.
+    let root = upper_bound.isqrt() as usize;
+    let base_primes = if root >= 2 { sieve(root)? } else { vec![] };
+
+    let mut composites =
+        FixedBitSet::try_with_capacity(segment_len).map_err(|_| Error::bad_memory(segment_len))?;
+    let mut primes = vec![];
+    let mut low: u64 = 2;
+    loop {
+        // The segment is [low, high], bit k standing for low + k.
+        let high = low.saturating_add(segment_len as u64 - 1).min(upper_bound);
+        cross_out_segment(&mut composites, &base_primes, low, high);
+        primes.extend((low..=high).filter(|k| !composites.contains((k - low) as usize)));
+
+        if high == upper_bound {
+            return Ok(primes);
+        }
+        low = high + 1;
+    }
+}
+
+/// Clears `composites` and crosses out the multiples of `base_primes` in the segment `[low, high]`,
+/// bit `k` standing for `low + k`. The base primes must be in increasing order and include every
+/// prime up to `sqrt(high)`.
+fn cross_out_segment(composites: &mut FixedBitSet, base_primes: &[usize], low: u64, high: u64) {
+    composites.as_mut_slice().fill(0);
+    for p in base_primes.iter().map(|p| *p as u64) {
+        // The base primes are at most sqrt(u64::MAX) in practice, so p * p can't overflow.
+        if p * p > high {
+            break;
+        }
+        let Some(first_in_segment) = low.div_ceil(p).checked_mul(p) else {
+            continue;
+        };
+        let mut multiple = first_in_segment.max(p * p);
+        while multiple <= high {
+            composites.insert((multiple - low) as usize);
+            multiple = match multiple.checked_add(p) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+}
+
+/// Sieves the primes up to a bound one segment at a time, for scanning a large range in chunks.
+///
+/// The base primes up to `sqrt(upper_bound)` are sieved once, and the segment's bitset and prime
+/// list are reused for every call to [SegmentSieve::next_segment], so nothing is allocated after
+/// construction.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// let mut segments = SegmentSieve::new(30, 10).unwrap();
+/// assert_eq!(segments.next_segment(), [2, 3, 5, 7, 11]);
+/// assert_eq!(segments.next_segment(), [13, 17, 19]);
+/// assert_eq!(segments.next_segment(), [23, 29]);
+/// assert!(segments.next_segment().is_empty());
+/// ```
+pub struct SegmentSieve {
+    upper_bound: usize,
+    segment_len: usize,
+    base_primes: Vec<usize>,
+    /// Bit `k` is set when `low + k` is composite.
+    composites: FixedBitSet,
+    primes: Vec<usize>,
+    /// The start of the next segment, or `None` once the upper bound has been passed.
+    low: Option<usize>,
+}
+
+impl SegmentSieve {
+    /// Sets up a sieve for the primes `p <= upper_bound` in segments of `segment_len` integers, or
+    /// of [optimal_segment_size] when `segment_len` is 0. The first segment starts at 2.
+    ///
+    /// # Errors
+    /// - [Error::BadBound] if `upper_bound < 2`.
+    /// - [Error::BadMemory] if the bitset for the segments can't be allocated.
+    pub fn new(upper_bound: usize, segment_len: usize) -> Result<Self> {
+        if upper_bound < 2 {
+            return Err(Error::BadBound(upper_bound));
+        }
+        let segment_len = if segment_len == 0 {
+            optimal_segment_size()
+        } else {
+            segment_len
+        };
+
+        let root = upper_bound.isqrt();
+        let base_primes = if root >= 2 { sieve(root)? } else { vec![] };
+        let composites = FixedBitSet::try_with_capacity(segment_len)
+            .map_err(|_| Error::bad_memory(segment_len))?;
+        // At most the odd integers of a segment are prime, plus 2 in the first.
+        let primes = Vec::with_capacity(segment_len / 2 + 1);
+
+        Ok(Self {
+            upper_bound,
+            segment_len,
+            base_primes,
+            composites,
+            primes,
+            low: Some(2),
+        })
+    }
+
+    /// Sieves the next segment and returns its primes in increasing order. The segments are
+    /// consecutive, so together they cover every prime up to the upper bound exactly once. Returns
+    /// an empty slice once the upper bound has been passed.
+    pub fn next_segment(&mut self) -> &[usize] {
+        self.primes.clear();
+        let Some(low) = self.low else {
+            return &self.primes;
+        };
+
+        let high = low.saturating_add(self.segment_len - 1).min(self.upper_bound);
+        cross_out_segment(&mut self.composites, &self.base_primes, low as u64, high as u64);
+        self.primes.extend((low..=high).filter(|k| !self.composites.contains(k - low)));
+
+        self.low = (high < self.upper_bound).then_some(high + 1);
+        &self.primes
+    }
+}
+
+/// Stitches together the primes from several sieves, such as segments sieved in parallel, into one
+/// increasing list without duplicates.
+///
+/// The lists may overlap and come in any order, and don't have to be sorted themselves.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// let merged = merge_prime_lists(&[vec![11, 13, 17], vec![2, 3, 5, 7, 11]]);
+/// assert_eq!(merged, [2, 3, 5, 7, 11, 13, 17]);
+/// ```
+pub fn merge_prime_lists(lists: &[Vec<usize>]) -> Vec<usize> {
+    let mut merged: Vec<usize> = lists.concat();
+    merged.sort_unstable();
+    merged.dedup();
+    merged
+}
+
+/// Counts the primes `p <= up_to`, usually written `pi(up_to)`. The primes are counted as they
+/// turn up rather than kept, so only the sieve's bitset takes memory.
+///
+/// # Errors
+/// The same as [SieveState::with_upper_bound].
+pub fn prime_count(up_to: usize) -> Result<usize> {
+    let mut state = SieveState::with_upper_bound(up_to)?;
+    let mut count = 0;
+    while state.sieve_once().is_some() {
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// The sum of the primes `p <= up_to`. It is about `up_to**2 / (2 ln(up_to))`, which outgrows
+/// `usize` for large bounds, so it is returned as a [BigInt].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(sum_of_primes(10).unwrap(), 2 + 3 + 5 + 7);
+/// ```
+///
+/// # Errors
+/// The same as [SieveState::with_upper_bound].
+pub fn sum_of_primes(up_to: usize) -> Result<BigInt> {
+    Ok(sieve(up_to)?.into_iter().fold(BigInt::new(), |sum, p| sum + p))
+}
+
+/// Counts the primes `p <= up_to` by their last decimal digit, so entry `d` is the number of
+/// primes ending in `d`.
+///
+/// Apart from 2 and 5, every prime ends in 1, 3, 7 or 9, and the four are about equally common.
+/// Consecutive primes avoid repeating their last digit more than chance would suggest, which
+/// is the "conspiracy" found by Lemke Oliver and Soundararajan.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(last_digit_counts(30).unwrap(), [0, 1, 1, 3, 0, 1, 0, 2, 0, 2]);
+/// ```
+///
+/// # Errors
+/// The same as [SieveState::with_upper_bound].
+pub fn last_digit_counts(up_to: usize) -> Result<[usize; 10]> {
+    let mut counts = [0; 10];
+    for p in sieve(up_to)? {
+        counts[p % 10] += 1;
+    }
+    Ok(counts)
+}
+
+/// The primes `p <= up_to` with `p = a mod m`, in increasing order.
+///
+/// By Dirichlet's theorem each class with `gcd(a, m) = 1` holds infinitely many primes, and they
+/// are spread evenly over the `phi(m)` such classes.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(primes_in_residue_class(30, 1, 4).unwrap(), [5, 13, 17, 29]);
+/// ```
+///
+/// # Errors
+/// - [Error::BadResidueClass] if `m = 0` or `gcd(a, m) > 1`.
+/// - Otherwise the same as [SieveState::with_upper_bound].
+pub fn primes_in_residue_class(up_to: usize, a: usize, m: usize) -> Result<Vec<usize>> {
+    if m == 0 || gcd(a, m) != 1 {
+        return Err(Error::BadResidueClass { a, m });
+    }
+
+    let residue = a % m;
+    let mut primes = sieve(up_to)?;
+    primes.retain(|p| p % m == residue);
+    Ok(primes)
+}
+
+fn gcd(mut a: usize, mut b: usize) -> usize {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The Fermat pseudoprimes to base `a` up to `up_to`: the composites `n` that pass Fermat's test
+/// with base `a`, in increasing order. The sieve says which `n` are composite.
+///
+/// [fermats_test][crate::primality::fermats_test] checks `a**n = a mod n`, which is weaker than
+/// `a**(n - 1) = 1 mod n` when `n` shares a factor with `a`, so `6` counts for base 3. Composites
+/// dividing `a`, or odd ones with `a = -1 mod n`, pass for any reason and are left out.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(pseudoprimes_to_base(2, 2000).unwrap(), [341, 561, 645, 1105, 1387, 1729, 1905]);
+/// ```
+///
+/// # Errors
+/// The same as [SieveState::with_upper_bound].
+///
+/// # Panics
+/// - `a == 0`
+pub fn pseudoprimes_to_base(a: u32, up_to: usize) -> Result<Vec<usize>> {
+    assert!(a != 0, "a must not be zero");
+
+    let primes = prime_mask(up_to)?;
+    let a = BigInt::from(a);
+    Ok((4..=up_to)
+        .filter(|n| !primes.contains(*n))
+        .filter(|n| {
+            let n = BigInt::from(*n);
+            // Bases of -1 give an error rather than a pass, so they are left out too.
+            !a.is_divisible(&n) && fermats_test_checked(&n, a.clone()) == Ok(true)
+        })
+        .collect())
+}
+
+/// Sieves up to `up_to` and returns the primes as a bitset for constant time lookups.
+///
+/// Bit `k` is set exactly when `k` is prime, so the set has `up_to + 1` bits and bits 0 and 1 are
+/// always clear. This is the opposite of the sieve's own bitset, where set bits are crossed out.
+/// Both bitsets exist while the mask is built, so this briefly needs twice the memory of the sieve.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// let mask = prime_mask(20).unwrap();
+/// assert!(mask.contains(17));
+/// assert!(!mask.contains(18));
+/// ```
+///
+/// # Errors
+/// The same as [SieveState::with_upper_bound].
+pub fn prime_mask(up_to: usize) -> Result<FixedBitSet> {
+    let mut state = SieveState::with_upper_bound(up_to)?;
+    // Only the crossed out bits are needed, so don't collect the primes.
+    state.run_with_min_prime(usize::MAX);
+
+    let len = up_to.checked_add(1).ok_or(Error::bad_memory(usize::MAX))?;
+    let mut mask = FixedBitSet::try_with_capacity(len).map_err(|_| Error::bad_memory(len))?;
+    for k in 2..=up_to {
+        if !state.composites.contains(k - 1) {
+            mask.insert(k);
+        }
+    }
+    Ok(mask)
+}
+
+/// Splits `[1, up_to]` into `buckets` intervals of equal width and counts the primes in each.
+///
+/// Bucket `i` holds the primes in `(i * up_to / buckets, (i + 1) * up_to / buckets]`, give or take
+/// rounding. By the prime number theorem the counts shrink roughly like `1 / ln(x)`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(prime_density(100, 4).unwrap(), vec![9, 6, 6, 4]);
+/// ```
+///
+/// # Errors
+/// The same as [SieveState::with_upper_bound].
+///
+/// # Panics
+/// - `buckets == 0`
+pub fn prime_density(up_to: usize, buckets: usize) -> Result<Vec<usize>> {
+    assert!(buckets > 0, "need at least one bucket");
+
+    let mut histogram = vec![0; buckets];
+    for p in sieve(up_to)? {
+        // Widened so that (p - 1) * buckets can't overflow.
+        let bucket = (p as u128 - 1) * buckets as u128 / up_to as u128;
+        histogram[bucket as usize] += 1;
+    }
+    Ok(histogram)
+}
+
+/// Euler's totient `phi(i)` for every `i <= up_to`, with entry `i` holding `phi(i)` and entry 0
+/// holding 0.
+///
+/// A linear sieve finds each composite exactly once, as `i p` with `p` its smallest prime factor.
+/// Then `phi(i p) = phi(i) p` if `p | i`, and `phi(i) (p - 1)` otherwise, so the whole table takes
+/// time proportional to `up_to`, where [euler_totient][crate::number_theory::euler_totient] would
+/// factor every number.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(totient_sieve(10).unwrap(), [0, 1, 1, 2, 2, 4, 2, 6, 4, 6, 4]);
+/// ```
+///
+/// # Errors
+/// - [Error::BadBound] if `up_to < 2`.
+/// - [Error::BadMemory] if the table can't be allocated.
+pub fn totient_sieve(up_to: usize) -> Result<Vec<usize>> {
+    let mut totients = try_table(up_to, 0)?;
+    totients[1] = 1;
+    linear_sieve(up_to, |n, p| {
+        totients[n * p] = if n % p == 0 {
+            totients[n] * p
+        } else {
+            totients[n] * (p - 1)
+        };
+    })?;
+    Ok(totients)
+}
+
+/// The Möbius function `mu(i)` for every `i <= up_to`, with entry `i` holding `mu(i)` and entry 0
+/// holding 0.
+///
+/// Uses the same linear sieve as [totient_sieve]: `mu(i p) = 0` if `p | i`, and `-mu(i)` otherwise.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(mobius_sieve(10).unwrap(), [0, 1, -1, -1, 0, -1, 1, -1, 0, 0, 1]);
+/// ```
+///
+/// # Errors
+/// The same as [totient_sieve].
+pub fn mobius_sieve(up_to: usize) -> Result<Vec<i8>> {
+    let mut mobius = try_table(up_to, 0)?;
+    mobius[1] = 1;
+    linear_sieve(up_to, |n, p| {
+        mobius[n * p] = if n % p == 0 { 0 } else { -mobius[n] };
+    })?;
+    Ok(mobius)
+}
+
+/// A table of `up_to + 1` entries, all `value`, for the sieves over `[0, up_to]`.
+fn try_table<T: Clone>(up_to: usize, value: T) -> Result<Vec<T>> {
+    if up_to < 2 {
+        return Err(Error::BadBound(up_to));
+    }
+    let bad_memory = || {
+        Error::bad_memory_bytes(up_to.saturating_add(1).saturating_mul(std::mem::size_of::<T>()))
+    };
+
+    let len = up_to.checked_add(1).ok_or_else(bad_memory)?;
+    let mut table = Vec::new();
+    table.try_reserve_exact(len).map_err(|_| bad_memory())?;
+    table.resize(len, value);
+    Ok(table)
+}
+
+/// Euler's linear sieve over `[2, up_to]`, calling `visit(n, p)` exactly once for each integer in
+/// the range, written as `n p` with `p` its smallest prime factor. A prime `p` comes as `1 p`.
+///
+/// `n` is always visited before `n p`, so `visit` can fill a table of a multiplicative function
+/// from the entry for `n`.
+fn linear_sieve(up_to: usize, mut visit: impl FnMut(usize, usize)) -> Result<()> {
+    let mut composites =
+        FixedBitSet::try_with_capacity(up_to).map_err(|_| Error::bad_memory(up_to))?;
+    let mut primes = vec![];
+    for n in 2..=up_to {
+        // Bit n - 1 stands for n, as in SieveState.
+        if !composites.contains(n - 1) {
+            primes.push(n);
+            visit(1, n);
+        }
+        for &p in &primes {
+            let Some(multiple) = n.checked_mul(p).filter(|m| *m <= up_to) else {
+                break;
+            };
+            composites.insert(multiple - 1);
+            visit(n, p);
+            // Past p, the smallest prime factor of n p' would be p, not p'.
+            if n % p == 0 {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The largest `n` that [is_prime_usize] looks up in its cached sieve rather than testing.
+pub const SMALL_PRIME_LIMIT: usize = 1 << 16;
+
+/// The [prime_mask] up to [SMALL_PRIME_LIMIT], sieved on first use unless it was loaded with
+/// [load_small_primes_from], and kept for the rest of the program. It takes 8 KB.
+static SMALL_PRIMES: OnceLock<FixedBitSet> = OnceLock::new();
+
+fn small_primes() -> &'static FixedBitSet {
+    SMALL_PRIMES.get_or_init(|| prime_mask(SMALL_PRIME_LIMIT).expect("the limit is a valid bound"))
+}
+
+/// The table [is_prime_usize] looks small numbers up in, as bytes that can be saved and handed to
+/// [load_small_primes_from] at a later start. Sieves the table first if that hasn't happened yet.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// let table = dump_small_primes();
+/// assert_eq!(table.len(), 8200);
+/// ```
+pub fn dump_small_primes() -> Vec<u8> {
+    small_primes().to_le_bytes()
+}
+
+/// Loads the table [is_prime_usize] uses from bytes written by [dump_small_primes], so it doesn't
+/// need to be sieved. A table embedded with `include_bytes!` can be loaded at startup like this.
+///
+/// The bytes are checked for size but not content, so they must come from [dump_small_primes].
+///
+/// # Errors
+/// - [Error::BadTable] if `bytes` is the wrong size for the table.
+/// - [Error::AlreadyLoaded] if the table was already loaded, or sieved by an earlier call to
+///   [is_prime_usize] or [dump_small_primes].
+pub fn load_small_primes_from(bytes: &[u8]) -> Result<()> {
+    load_small_primes_into(&SMALL_PRIMES, bytes)
+}
+
+fn load_small_primes_into(cell: &OnceLock<FixedBitSet>, bytes: &[u8]) -> Result<()> {
+    let table = FixedBitSet::from_le_bytes(SMALL_PRIME_LIMIT + 1, bytes).ok_or(Error::BadTable)?;
+    cell.set(table).map_err(|_| Error::AlreadyLoaded)
+}
+
+/// Deterministic primality test for `usize`, without any bignum arithmetic.
+///
+/// Up to [SMALL_PRIME_LIMIT] the answer is looked up in a sieve that is built once and cached.
+/// Anything larger goes to [is_prime_u64].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert!(is_prime_usize(65521));
+/// assert!(!is_prime_usize(65535));
+/// ```
+pub fn is_prime_usize(n: usize) -> bool {
+    if n <= SMALL_PRIME_LIMIT {
+        small_primes().contains(n)
+    } else {
+        is_prime_u64(n as u64)
+    }
+}
+
+/// The largest prime `p < n`, or `None` if `n <= 2`.
+///
+/// Rather than sieving up to `n`, this walks down from `n - 1` testing each candidate with
+/// [is_prime_usize]. The largest prime gap below `2**64` is 1550, so even for huge `n` that bounds
+/// the number of tests.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(largest_prime_below(20), Some(19));
+/// assert_eq!(largest_prime_below(2), None);
+/// ```
+pub fn largest_prime_below(n: usize) -> Option<usize> {
+    (2..n).rev().find(|k| is_prime_usize(*k))
+}
+
+/// The largest prime [prime_index] will sieve up to, about `10**9`. Sieving this far takes around
+/// 130 MB for the bitset, as the primes are only counted, and several seconds.
+pub const PRIME_INDEX_LIMIT: usize = 1 << 30;
+
+/// Returns the 1-based index of `p` among the primes, so `2` has index 1, `3` index 2 and so on.
+///
+/// Returns `None` if `p` isn't prime, or if it is larger than [PRIME_INDEX_LIMIT] since the index
+/// is found by sieving all the way up to `p`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::esieve::*;
+/// assert_eq!(prime_index(&13.into()), Some(6));
+/// assert_eq!(prime_index(&15.into()), None);
+/// ```
+pub fn prime_index(p: &BigInt) -> Option<usize> {
+    let p = p.to_usize().filter(|p| *p <= PRIME_INDEX_LIMIT)?;
+    if !is_prime_u64(p as u64) {
+        return None;
+    }
+
+    let count = prime_count(p).expect("p is a prime below the limit, so the bound is valid");
+    Some(count)
+}
@@ -0,0 +1,95 @@
+//! A small fixed-size bitset, just enough for the sieve.
+
+use std::collections::TryReserveError;
+
+const BLOCK_BITS: usize = u64::BITS as usize;
+
+/// A set of bits with a length fixed at construction.
+///
+/// Bits are numbered from `0` to `len - 1`. All bits start out cleared.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FixedBitSet {
+    blocks: Vec<u64>,
+    len: usize,
+}
+
+impl FixedBitSet {
+    /// Creates a bitset of `len` cleared bits.
+    ///
+    /// Fails instead of aborting if the memory for the bits can't be reserved, which matters for
+    /// sieves with very large bounds.
+    pub fn try_with_capacity(len: usize) -> Result<Self, TryReserveError> {
+        let block_count = len.div_ceil(BLOCK_BITS);
+        let mut blocks = Vec::new();
+        blocks.try_reserve_exact(block_count)?;
+        blocks.resize(block_count, 0);
+        Ok(Self { blocks, len })
+    }
+
+    /// The number of bits in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the set holds no bits at all.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if `bit` is set. Bits beyond the length are never set.
+    pub fn contains(&self, bit: usize) -> bool {
+        bit < self.len && self.blocks[bit / BLOCK_BITS] & (1 << (bit % BLOCK_BITS)) != 0
+    }
+
+    /// Sets `bit`.
+    ///
+    /// # Panics
+    /// - `bit >= self.len()`
+    pub fn insert(&mut self, bit: usize) {
+        self.set(bit, true);
+    }
+
+    /// Sets or clears `bit`.
+    ///
+    /// # Panics
+    /// - `bit >= self.len()`
+    pub fn set(&mut self, bit: usize, enabled: bool) {
+        assert!(bit < self.len, "bit {} out of range for length {}", bit, self.len);
+        let mask = 1 << (bit % BLOCK_BITS);
+        if enabled {
+            self.blocks[bit / BLOCK_BITS] |= mask;
+        } else {
+            self.blocks[bit / BLOCK_BITS] &= !mask;
+        }
+    }
+
+    /// The underlying blocks, bit `i` being bit `i % 64` of block `i / 64`. Bits past the length in
+    /// the last block are ignored.
+    pub fn as_mut_slice(&mut self) -> &mut [u64] {
+        &mut self.blocks
+    }
+
+    /// The blocks as little-endian bytes, 8 per block, for storing the set.
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        self.blocks.iter().flat_map(|block| block.to_le_bytes()).collect()
+    }
+
+    /// Reads back a set of `len` bits written by [FixedBitSet::to_le_bytes].
+    ///
+    /// Returns `None` if `bytes` is the wrong size for `len` bits, or sets bits past the length.
+    pub fn from_le_bytes(len: usize, bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != len.div_ceil(BLOCK_BITS) * 8 {
+            return None;
+        }
+
+        let blocks: Vec<u64> = bytes
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        let used_bits = len % BLOCK_BITS;
+        if used_bits != 0 && blocks.last().is_some_and(|last| last >> used_bits != 0) {
+            return None;
+        }
+        Some(Self { blocks, len })
+    }
+}
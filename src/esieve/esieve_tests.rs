@@ -0,0 +1,683 @@
+use crate::{
+    number_theory::{euler_totient, mobius},
+    primality::FIRST_100_PRIMES,
+    BigInt,
+};
+
+use super::*;
+
+fn first_primes_up_to(bound: usize) -> Vec<usize> {
+    FIRST_100_PRIMES
+        .iter()
+        .map(|p| *p as usize)
+        .take_while(|p| *p <= bound)
+        .collect()
+}
+
+/// Plain sieve of Eratosthenes without any tricks, to compare against.
+fn naive_sieve(bound: usize) -> Vec<usize> {
+    let mut is_composite = vec![false; bound + 1];
+    let mut primes = vec![];
+    for n in 2..=bound {
+        if !is_composite[n] {
+            primes.push(n);
+            for multiple in (n * n..=bound).step_by(n) {
+                is_composite[multiple] = true;
+            }
+        }
+    }
+    primes
+}
+
+#[test]
+fn test_sieve_first_100_primes() {
+    let primes = sieve(541).unwrap();
+    assert_eq!(primes, first_primes_up_to(541));
+    assert_eq!(primes.len(), 100);
+}
+
+#[test]
+fn test_sieve_bound_near_prime() {
+    // Bound on a prime, just above it and just below it.
+    for bound in [96, 97, 98] {
+        assert_eq!(sieve(bound).unwrap(), first_primes_up_to(bound));
+    }
+}
+
+#[test]
+fn test_sieve_bound_on_square() {
+    // The square of the last divisor sits exactly on the bound and must be crossed out.
+    for bound in [25, 49, 120, 121, 122, 529] {
+        assert_eq!(sieve(bound).unwrap(), first_primes_up_to(bound));
+    }
+}
+
+#[test]
+fn test_sieve_bad_bound() {
+    assert_eq!(SieveState::with_upper_bound(0).err(), Some(Error::BadBound(0)));
+    assert_eq!(SieveState::with_upper_bound(1).err(), Some(Error::BadBound(1)));
+    assert_eq!(
+        Error::BadBound(1).to_string(),
+        "the upper bound 1 is below 2, there is nothing to sieve"
+    );
+}
+
+#[test]
+fn test_error_as_dyn_error() {
+    fn primes_boxed(bound: usize) -> std::result::Result<Vec<usize>, Box<dyn std::error::Error>> {
+        Ok(sieve(bound)?)
+    }
+
+    assert_eq!(primes_boxed(10).unwrap(), [2, 3, 5, 7]);
+    let error = primes_boxed(0).unwrap_err();
+    assert_eq!(error.to_string(), "the upper bound 0 is below 2, there is nothing to sieve");
+    assert_eq!(error.downcast_ref::<Error>(), Some(&Error::BadBound(0)));
+}
+
+#[test]
+fn test_sieve_unallocatable_bound() {
+    // usize::MAX bits, rounded up to whole 8 byte blocks.
+    let requested_bytes = usize::MAX.div_ceil(64) * 8;
+    let limit = isize::MAX as usize;
+    assert_eq!(
+        SieveState::with_upper_bound(usize::MAX).err(),
+        Some(Error::BadMemory { requested_bytes, limit })
+    );
+    assert_eq!(
+        Error::BadMemory { requested_bytes: 1024, limit: 512 }.to_string(),
+        "couldn't allocate 1024 bytes for the sieve, the limit is 512 bytes"
+    );
+}
+
+#[test]
+fn test_find_next_divisor_stops_at_bound() {
+    let mut state = SieveState::with_upper_bound(13).unwrap();
+    state.run();
+    assert_eq!(state.last_divisor, 13);
+    assert_eq!(state.find_next_divisor(), None);
+    assert_eq!(state.sieve_once(), None);
+}
+
+#[test]
+fn test_smallest_bounds() {
+    let wheels = [Wheel::None, Wheel::Mod6, Wheel::Mod30, Wheel::Mod210];
+    for (bound, expected) in [(2, &[2][..]), (3, &[2, 3]), (4, &[2, 3])] {
+        for wheel in wheels {
+            let mut state = SieveState::with_upper_bound_and_wheel(bound, wheel).unwrap();
+            state.run();
+            assert_eq!(state.primes_found(), expected, "bound = {}, {:?}", bound, wheel);
+            assert_eq!(state.step(), Ok(None));
+        }
+        assert_eq!(sieve(bound).unwrap(), expected);
+        assert_eq!(prime_count(bound).unwrap(), expected.len());
+        let expected_u64: Vec<u64> = expected.iter().map(|p| *p as u64).collect();
+        assert_eq!(sieve_u64(bound as u64).unwrap(), expected_u64);
+        assert_eq!(SegmentSieve::new(bound, 0).unwrap().next_segment(), expected);
+    }
+
+    let mut state = SieveState::with_upper_bound(2).unwrap();
+    assert_eq!(state.step(), Ok(Some(2)));
+    assert_eq!(state.step(), Ok(None));
+    assert_eq!(state.primes_found(), [2]);
+}
+
+#[test]
+fn test_mark_multiples_of_one() {
+    let mut state = SieveState::with_upper_bound(10).unwrap();
+    state.mark_multiples_as_composite(1);
+    state.run();
+    assert_eq!(state.primes_found(), &[2, 3, 5, 7]);
+}
+
+#[test]
+fn test_primes_as_bigint() {
+    let mut state = SieveState::with_upper_bound(100_000).unwrap();
+    assert!(state.primes_as_bigint().is_empty());
+
+    state.run();
+    let primes = state.primes_as_bigint();
+    assert_eq!(primes.len(), state.primes_found().len());
+    for (big, p) in primes.iter().zip(state.primes_found()) {
+        assert_eq!(*big, *p);
+    }
+}
+
+#[test]
+fn test_run_with_min_prime() {
+    let mut state = SieveState::with_upper_bound(100).unwrap();
+    state.run_with_min_prime(50);
+    assert_eq!(state.primes_found(), &[53, 59, 61, 67, 71, 73, 79, 83, 89, 97]);
+
+    // Discarding the small primes doesn't change which larger ones are found.
+    for min_prime in [0, 2, 7, 11, 12, 1000] {
+        let mut state = SieveState::with_upper_bound(1000).unwrap();
+        state.run_with_min_prime(min_prime);
+        let expected: Vec<usize> = naive_sieve(1000)
+            .into_iter()
+            .filter(|p| *p >= min_prime)
+            .collect();
+        assert_eq!(state.primes_found(), expected, "min_prime = {}", min_prime);
+    }
+}
+
+#[test]
+fn test_primes_are_preallocated() {
+    for bound in [2, 3, 10, 113, 1000, 65536, 1_000_000] {
+        let expected = prime_count_upper_bound(bound);
+        let mut state = SieveState::with_upper_bound(bound).unwrap();
+        state.run();
+        assert_eq!(state.primes_found(), naive_sieve(bound), "bound = {}", bound);
+        // The list never had to grow past what was reserved.
+        assert!(state.primes_found().len() <= expected, "bound = {}", bound);
+        assert_eq!(state.primes.capacity(), expected, "bound = {}", bound);
+    }
+
+    // Nothing is reserved when no primes are kept.
+    let mut state = SieveState::with_upper_bound(1000).unwrap();
+    state.run_with_min_prime(usize::MAX);
+    assert_eq!(state.primes.capacity(), 0);
+
+    // Only the primes from min_prime on get room.
+    let mut state = SieveState::with_upper_bound(1_000_000).unwrap();
+    state.run_with_min_prime(900_000);
+    assert!(state.primes.capacity() >= state.primes_found().len());
+    assert!(state.primes.capacity() < prime_count_upper_bound(1_000_000) / 2);
+}
+
+#[test]
+fn test_prime_count_lower_bound() {
+    for bound in [2, 16, 17, 18, 100, 113, 1000, 65536, 1_000_000] {
+        let count = naive_sieve(bound).len();
+        assert!(prime_count_lower_bound(bound) <= count, "bound = {}", bound);
+        assert!(prime_count_upper_bound(bound) >= count, "bound = {}", bound);
+    }
+}
+
+#[test]
+fn test_primes_in_residue_class() {
+    assert_eq!(primes_in_residue_class(30, 1, 4), Ok(vec![5, 13, 17, 29]));
+    assert_eq!(primes_in_residue_class(30, 3, 4), Ok(vec![3, 7, 11, 19, 23]));
+    assert_eq!(primes_in_residue_class(30, 7, 4), primes_in_residue_class(30, 3, 4));
+    assert_eq!(primes_in_residue_class(30, 0, 1), sieve(30));
+    assert_eq!(primes_in_residue_class(1, 1, 4), Err(Error::BadBound(1)));
+
+    // Every odd prime is in exactly one of the classes coprime to 10.
+    let classes: Vec<Vec<usize>> = [1, 3, 7, 9]
+        .iter()
+        .map(|a| primes_in_residue_class(10_000, *a, 10).unwrap())
+        .collect();
+    let mut expected = naive_sieve(10_000);
+    expected.retain(|p| *p != 2 && *p != 5);
+    assert_eq!(merge_prime_lists(&classes), expected);
+
+    for (a, m) in [(2, 4), (0, 5), (6, 9), (1, 0)] {
+        assert_eq!(
+            primes_in_residue_class(100, a, m),
+            Err(Error::BadResidueClass { a, m })
+        );
+    }
+    assert_eq!(
+        Error::BadResidueClass { a: 2, m: 4 }.to_string(),
+        "2 mod 4 isn't a residue class coprime to the modulus"
+    );
+}
+
+#[test]
+fn test_sieve_u64() {
+    let segment = SEGMENT_LEN as u64;
+    for bound in [2, 3, 4, 30, 1000, segment - 1, segment, segment + 1, 3 * segment + 5] {
+        let expected: Vec<u64> = sieve(bound as usize)
+            .unwrap()
+            .into_iter()
+            .map(|p| p as u64)
+            .collect();
+        assert_eq!(sieve_u64(bound).unwrap(), expected, "bound = {}", bound);
+    }
+
+    assert_eq!(sieve_u64(0), Err(Error::BadBound(0)));
+    assert_eq!(sieve_u64(1), Err(Error::BadBound(1)));
+}
+
+#[test]
+fn test_step() {
+    for bound in [2, 10, 97, 1000, 65536] {
+        let mut stepped = SieveState::with_upper_bound(bound).unwrap();
+        let mut returned = vec![];
+        while let Some(p) = stepped.step().unwrap() {
+            assert_eq!(stepped.primes_found().last(), Some(&p));
+            returned.push(p);
+        }
+
+        let mut state = SieveState::with_upper_bound(bound).unwrap();
+        state.run();
+        assert_eq!(returned, state.primes_found(), "bound = {}", bound);
+        assert_eq!(stepped.primes_found(), state.primes_found(), "bound = {}", bound);
+        assert_eq!(stepped.stats(), state.stats(), "bound = {}", bound);
+        assert_eq!(stepped.step(), Ok(None));
+    }
+}
+
+#[test]
+fn test_progress() {
+    let mut state = SieveState::with_upper_bound(100_000).unwrap();
+    assert!(state.progress() < 0.0001);
+
+    let mut last = state.progress();
+    for _ in 0..100 {
+        state.sieve_once();
+        assert!(state.progress() > last);
+        last = state.progress();
+    }
+    assert_eq!(state.progress(), 541.0 / 100_000.0);
+
+    state.run();
+    assert_eq!(state.progress(), 99_991.0 / 100_000.0);
+}
+
+#[test]
+fn test_sum_of_primes() {
+    assert_eq!(sum_of_primes(2), Ok(BigInt::from(2)));
+    assert_eq!(sum_of_primes(10), Ok(BigInt::from(17)));
+    assert_eq!(sum_of_primes(100), Ok(BigInt::from(1060)));
+    assert_eq!(sum_of_primes(2_000_000), Ok(BigInt::from(142_913_828_922u64)));
+    assert_eq!(sum_of_primes(1), Err(Error::BadBound(1)));
+
+    let first_100: u32 = FIRST_100_PRIMES.iter().sum();
+    assert_eq!(sum_of_primes(541), Ok(BigInt::from(first_100)));
+}
+
+#[test]
+fn test_segmented_sieve() {
+    assert_eq!(optimal_segment_size(), 32 * 1024 * 8);
+
+    for bound in [2, 30, 1000, 100_000, SEGMENT_LEN as u64 + 1] {
+        let expected = segmented_sieve(bound, 0).unwrap();
+        assert_eq!(expected, sieve_u64(bound).unwrap());
+        for segment_len in [1, 2, 63, 64, 1000, optimal_segment_size()] {
+            assert_eq!(
+                segmented_sieve(bound, segment_len).unwrap(),
+                expected,
+                "bound = {}, segment_len = {}",
+                bound,
+                segment_len
+            );
+        }
+    }
+
+    assert_eq!(segmented_sieve(1, 64), Err(Error::BadBound(1)));
+}
+
+#[test]
+fn test_sieve_for_count() {
+    let first_25: Vec<usize> = FIRST_100_PRIMES[..25].iter().map(|p| *p as usize).collect();
+    assert_eq!(sieve_for_count(25).unwrap(), first_25);
+
+    for count in 0..=100 {
+        let primes = sieve_for_count(count).unwrap();
+        assert_eq!(primes.len(), count);
+        assert!(primes.iter().zip(FIRST_100_PRIMES).all(|(p, q)| *p == *q as usize));
+    }
+
+    let primes = sieve_for_count(100_000).unwrap();
+    assert_eq!(primes.len(), 100_000);
+    assert_eq!(primes.last(), Some(&1_299_709));
+}
+
+#[test]
+fn test_segment_sieve() {
+    for bound in [2, 30, 1000, 100_000] {
+        let expected = sieve(bound).unwrap();
+        for segment_len in [1, 2, 63, 64, 1000, 0] {
+            let mut segments = SegmentSieve::new(bound, segment_len).unwrap();
+            // The segments cover [2, bound].
+            let len = if segment_len == 0 { optimal_segment_size() } else { segment_len };
+            let mut found = vec![];
+            for _ in 0..(bound - 1).div_ceil(len) {
+                found.extend_from_slice(segments.next_segment());
+            }
+            assert_eq!(found, expected, "bound = {}, segment_len = {}", bound, segment_len);
+            assert!(segments.next_segment().is_empty());
+        }
+    }
+
+    // Stays empty once the bound is passed.
+    let mut segments = SegmentSieve::new(10, 100).unwrap();
+    assert_eq!(segments.next_segment(), [2, 3, 5, 7]);
+    assert!(segments.next_segment().is_empty());
+    assert!(segments.next_segment().is_empty());
+
+    assert!(matches!(SegmentSieve::new(1, 64), Err(Error::BadBound(1))));
+}
+
+#[test]
+fn test_totient_sieve() {
+    let totients = totient_sieve(10_000).unwrap();
+    assert_eq!(totients.len(), 10_001);
+    assert_eq!(totients[..13], [0, 1, 1, 2, 2, 4, 2, 6, 4, 6, 4, 10, 4]);
+    for n in (1..=10_000).step_by(37).chain([561, 1024, 9973, 10_000]) {
+        assert_eq!(euler_totient(&BigInt::from(n)), totients[n], "n = {}", n);
+    }
+
+    assert_eq!(totient_sieve(2).unwrap(), [0, 1, 1]);
+    assert_eq!(totient_sieve(1), Err(Error::BadBound(1)));
+}
+
+#[test]
+fn test_mobius_sieve() {
+    let mobius_values = mobius_sieve(10_000).unwrap();
+    assert_eq!(mobius_values.len(), 10_001);
+    assert_eq!(mobius_values[..13], [0, 1, -1, -1, 0, -1, 1, -1, 0, 0, 1, -1, 0]);
+    for n in (1..=10_000).step_by(37).chain([210, 561, 1024, 2310, 9973]) {
+        assert_eq!(mobius(&BigInt::from(n)), mobius_values[n] as i32, "n = {}", n);
+    }
+    // The Mertens function M(10**4) is -23.
+    assert_eq!(mobius_values.iter().map(|mu| *mu as i32).sum::<i32>(), -23);
+
+    assert_eq!(mobius_sieve(2).unwrap(), [0, 1, -1]);
+    assert_eq!(mobius_sieve(0), Err(Error::BadBound(0)));
+}
+
+#[test]
+fn test_pseudoprimes_to_base() {
+    let base_two = pseudoprimes_to_base(2, 100_000).unwrap();
+    assert!(base_two.contains(&341));
+    assert_eq!(base_two[..4], [341, 561, 645, 1105]);
+    // All of them odd, the first even one is 161038.
+    assert_eq!(base_two.len(), 78);
+
+    // Carmichael numbers are pseudoprimes to every base.
+    for a in [2, 3, 5, 7, 10] {
+        assert!(pseudoprimes_to_base(a, 1000).unwrap().contains(&561), "a = {}", a);
+    }
+    assert_eq!(pseudoprimes_to_base(3, 300).unwrap(), [6, 66, 91, 121, 286]);
+
+    assert_eq!(pseudoprimes_to_base(2, 1), Err(Error::BadBound(1)));
+    assert!(pseudoprimes_to_base(2, 3).unwrap().is_empty());
+}
+
+#[test]
+#[should_panic]
+fn test_pseudoprimes_to_base_zero_base() {
+    let _ = pseudoprimes_to_base(0, 100);
+}
+
+#[test]
+fn test_last_digit_counts() {
+    let counts = last_digit_counts(1000).unwrap();
+    assert_eq!(counts, [0, 40, 1, 42, 0, 1, 0, 46, 0, 38]);
+    assert_eq!(counts.iter().sum::<usize>(), 168);
+
+    let counts = last_digit_counts(1_000_000).unwrap();
+    for digit in [0, 4, 6, 8] {
+        assert_eq!(counts[digit], 0);
+    }
+    assert_eq!((counts[2], counts[5]), (1, 1));
+    // Each of 1, 3, 7 and 9 gets close to a quarter of the 78498 primes.
+    for digit in [1, 3, 7, 9] {
+        assert!(counts[digit].abs_diff(78498 / 4) < 200, "{:?}", counts);
+    }
+
+    assert_eq!(last_digit_counts(1), Err(Error::BadBound(1)));
+}
+
+#[test]
+fn test_merge_prime_lists() {
+    assert_eq!(merge_prime_lists(&[]), [] as [usize; 0]);
+    assert_eq!(merge_prime_lists(&[vec![], vec![2]]), [2]);
+    // Disjoint, out of order.
+    assert_eq!(
+        merge_prime_lists(&[vec![29, 31], vec![2, 3, 5], vec![11, 13]]),
+        [2, 3, 5, 11, 13, 29, 31]
+    );
+    // Overlapping and nested.
+    assert_eq!(
+        merge_prime_lists(&[vec![2, 3, 5, 7], vec![5, 7, 11], vec![3, 5], vec![7, 11, 13]]),
+        [2, 3, 5, 7, 11, 13]
+    );
+
+    // Overlapping segments of a larger sieve give back the whole sieve.
+    let primes = sieve(10_000).unwrap();
+    let segments: Vec<Vec<usize>> = [(0, 4000), (3000, 7000), (6999, 10_001)]
+        .iter()
+        .map(|(lo, hi)| primes.iter().copied().filter(|p| lo <= p && p < hi).collect())
+        .collect();
+    assert_eq!(merge_prime_lists(&segments), primes);
+}
+
+#[test]
+fn test_prime_mask() {
+    let mask = prime_mask(100).unwrap();
+    assert_eq!(mask.len(), 101);
+    assert!(mask.contains(17));
+    assert!(!mask.contains(18));
+    assert!(!mask.contains(0));
+    assert!(!mask.contains(1));
+    assert!(mask.contains(2));
+    assert!(mask.contains(97));
+    assert!(!mask.contains(101));
+
+    let primes = naive_sieve(10_000);
+    let mask = prime_mask(10_000).unwrap();
+    for k in 0..=10_000 {
+        assert_eq!(mask.contains(k), primes.binary_search(&k).is_ok(), "k = {}", k);
+    }
+
+    assert_eq!(prime_mask(1), Err(Error::BadBound(1)));
+}
+
+#[test]
+fn test_is_prime_usize() {
+    for p in FIRST_100_PRIMES {
+        assert!(is_prime_usize(*p as usize), "p = {}", p);
+    }
+    for n in [0, 1, 4, 9, 91, 561, 65535, 65536, 65537 * 65537, 3_215_031_751] {
+        assert_eq!(is_prime_usize(n), is_prime_u64(n as u64), "n = {}", n);
+    }
+    assert!(!is_prime_usize(561));
+    assert!(!is_prime_usize(65536));
+
+    // Both sides of the cached range.
+    for n in SMALL_PRIME_LIMIT - 1000..SMALL_PRIME_LIMIT + 1000 {
+        assert_eq!(is_prime_usize(n), is_prime_u64(n as u64), "n = {}", n);
+    }
+    assert!(is_prime_usize(65537));
+    assert!(is_prime_usize(2_305_843_009_213_693_951));
+}
+
+#[test]
+fn test_dump_and_load_small_primes() {
+    let table = dump_small_primes();
+    assert_eq!(table.len(), (SMALL_PRIME_LIMIT + 1).div_ceil(64) * 8);
+
+    // The shared table is sieved by now, so a fresh one stands in for a new process.
+    assert_eq!(load_small_primes_from(&table), Err(Error::AlreadyLoaded));
+    let fresh = OnceLock::new();
+    assert_eq!(load_small_primes_into(&fresh, &table), Ok(()));
+    assert_eq!(fresh.get(), Some(small_primes()));
+    assert_eq!(load_small_primes_into(&fresh, &table), Err(Error::AlreadyLoaded));
+
+    let fresh = OnceLock::new();
+    assert_eq!(load_small_primes_into(&fresh, &table[1..]), Err(Error::BadTable));
+    assert_eq!(load_small_primes_into(&fresh, &[]), Err(Error::BadTable));
+    let mut past_the_end = table.clone();
+    *past_the_end.last_mut().unwrap() = 0x80;
+    assert_eq!(load_small_primes_into(&fresh, &past_the_end), Err(Error::BadTable));
+    assert!(fresh.get().is_none());
+}
+
+#[test]
+fn test_nth_prime_in_sieve() {
+    let mut state = SieveState::with_upper_bound(20).unwrap();
+    assert_eq!(state.nth_prime_in_sieve(1), None);
+    state.run();
+    assert_eq!(state.nth_prime_in_sieve(0), None);
+    assert_eq!(state.nth_prime_in_sieve(1), Some(2));
+    assert_eq!(state.nth_prime_in_sieve(4), Some(7));
+    assert_eq!(state.nth_prime_in_sieve(8), Some(19));
+    assert_eq!(state.nth_prime_in_sieve(9), None);
+    assert_eq!(state.nth_prime_in_sieve(100), None);
+
+    let mut state = SieveState::with_upper_bound(541).unwrap();
+    state.run();
+    for (k, p) in (1..).zip(FIRST_100_PRIMES) {
+        assert_eq!(state.nth_prime_in_sieve(k), Some(*p as usize));
+    }
+}
+
+#[test]
+fn test_largest_prime_below() {
+    assert_eq!(largest_prime_below(20), Some(19));
+    assert_eq!(largest_prime_below(19), Some(17));
+    assert_eq!(largest_prime_below(3), Some(2));
+    for n in [0, 1, 2] {
+        assert_eq!(largest_prime_below(n), None);
+    }
+
+    let primes = naive_sieve(1000);
+    for n in 3..=1000 {
+        let expected = primes.iter().rev().find(|p| **p < n).copied();
+        assert_eq!(largest_prime_below(n), expected, "n = {}", n);
+    }
+
+    // 2**64 - 59 is the largest prime that fits in 64 bits.
+    assert_eq!(largest_prime_below(usize::MAX), Some(usize::MAX - 58));
+}
+
+#[test]
+fn test_is_sorted() {
+    for bound in [2, 30, 210, 1000, 100_000] {
+        for wheel in [Wheel::None, Wheel::Mod210] {
+            let mut state = SieveState::with_upper_bound_and_wheel(bound, wheel).unwrap();
+            assert!(state.is_sorted());
+            state.run();
+            assert!(state.is_sorted(), "{:?}, bound = {}", wheel, bound);
+        }
+    }
+
+    let mut state = SieveState::with_upper_bound(30).unwrap();
+    state.run_with_min_prime(10);
+    assert!(state.is_sorted());
+    state.primes.swap(0, 1);
+    assert!(!state.is_sorted());
+}
+
+#[test]
+fn test_stats() {
+    let mut state = SieveState::with_upper_bound(30).unwrap();
+    assert_eq!(state.stats(), SieveStats::default());
+    state.run();
+    // Every integer in [2, 30] is looked at once, the primes among them are the ones found.
+    let stats = state.stats();
+    assert_eq!(stats.divisors_tested, 29);
+    assert_eq!(state.primes_found().len(), 10);
+    // Only the wheel primes have multiples below 30.
+    assert_eq!(stats.marks_performed, 0);
+
+    let mut state = SieveState::with_upper_bound(200).unwrap();
+    state.run();
+    // 121, 132, ..., 198 for 11 and 169, 182, 195 for 13.
+    assert_eq!(
+        state.stats(),
+        SieveStats {
+            marks_performed: 11,
+            divisors_tested: 199,
+        }
+    );
+}
+
+#[test]
+fn test_sieve_matches_naive_sieve() {
+    for bound in 2..=1000 {
+        assert_eq!(sieve(bound).unwrap(), naive_sieve(bound), "bound = {}", bound);
+    }
+
+    // Around the wheel circumference and where the wheel blocks repeat.
+    for bound in [209, 210, 211, 6719, 6720, 6721, 100_000] {
+        assert_eq!(sieve(bound).unwrap(), naive_sieve(bound), "bound = {}", bound);
+    }
+}
+
+#[test]
+fn test_wheel() {
+    for wheel in [Wheel::None, Wheel::Mod6, Wheel::Mod30, Wheel::Mod210] {
+        let blocks = wheel.blocks();
+        for bit in 0..64 * blocks.len() {
+            let crossed_out = blocks[bit / 64] & (1 << (bit % 64)) != 0;
+            let expected = wheel.primes().iter().any(|p| (bit + 1) % p == 0);
+            assert_eq!(crossed_out, expected, "{:?}, bit = {}", wheel, bit);
+        }
+        // The pattern has to tile, so its length is a multiple of the primes' product.
+        let period: usize = wheel.primes().iter().product();
+        assert_eq!(64 * blocks.len() % period, 0, "{:?}", wheel);
+    }
+}
+
+#[test]
+fn test_sieve_with_wheel() {
+    let expected = naive_sieve(10_000);
+    for wheel in [Wheel::None, Wheel::Mod6, Wheel::Mod30, Wheel::Mod210] {
+        assert_eq!(sieve_with_wheel(10_000, wheel).unwrap(), expected, "{:?}", wheel);
+        for bound in 2..=300 {
+            assert_eq!(
+                sieve_with_wheel(bound, wheel).unwrap(),
+                naive_sieve(bound),
+                "{:?}, bound = {}",
+                wheel,
+                bound
+            );
+        }
+    }
+    assert_eq!(sieve_with_wheel(1, Wheel::None), Err(Error::BadBound(1)));
+}
+
+#[test]
+fn test_prime_index() {
+    assert_eq!(prime_index(&BigInt::from(2)), Some(1));
+    assert_eq!(prime_index(&BigInt::from(13)), Some(6));
+
+    for (i, p) in FIRST_100_PRIMES.iter().enumerate() {
+        assert_eq!(prime_index(&BigInt::from(*p)), Some(i + 1));
+    }
+
+    // 104729 is the 10000th prime.
+    assert_eq!(prime_index(&BigInt::from(104729)), Some(10000));
+
+    for n in [-7, 0, 1, 4, 561, 104730] {
+        assert_eq!(prime_index(&BigInt::from(n)), None);
+    }
+
+    // Way past the limit, a prime but too large to index.
+    let mersenne = (BigInt::from(1) << 61) - 1;
+    assert_eq!(prime_index(&mersenne), None);
+}
+
+#[test]
+fn test_prime_count() {
+    assert_eq!(prime_count(2), Ok(1));
+    assert_eq!(prime_count(100), Ok(25));
+    assert_eq!(prime_count(1_000_000), Ok(78498));
+    assert_eq!(prime_count(1), Err(Error::BadBound(1)));
+}
+
+#[test]
+fn test_prime_density() {
+    for (up_to, buckets) in [(100, 4), (10_000, 10), (1000, 7), (10, 50)] {
+        let histogram = prime_density(up_to, buckets).unwrap();
+        assert_eq!(histogram.len(), buckets);
+        assert_eq!(histogram.iter().sum::<usize>(), prime_count(up_to).unwrap());
+    }
+
+    let histogram = prime_density(10_000, 10).unwrap();
+    assert!(histogram[0] > histogram[9]);
+    // pi(1000) = 168
+    assert_eq!(histogram[0], 168);
+
+    assert_eq!(prime_density(100, 1), Ok(vec![25]));
+    assert_eq!(prime_density(0, 1), Err(Error::BadBound(0)));
+}
+
+#[test]
+#[should_panic]
+fn test_prime_density_no_buckets() {
+    let _ = prime_density(100, 0);
+}
@@ -0,0 +1,1014 @@
+//! # Factorization
+//! Methods for splitting composite integers into factors. As with the primality tests these are
+//! reference implementations for learning, GMP has nothing comparable but dedicated tools like
+//! `msieve` or `yafu` are in another league.
+
+mod gf2;
+
+#[cfg(test)]
+mod factorization_tests;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    num::{NonZeroU32, NonZeroUsize},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use rug::{integer::IsPrime, ops::Pow};
+
+use gf2::BitVector;
+
+use super::*;
+use crate::{
+    esieve,
+    primality::{
+        miller_rabin_rounds, mod_pow_u64, MillerRabinOracle, Primality, PrimalityOracle,
+        FIRST_100_PRIMES,
+    },
+};
+
+/// What [factorize_checked] found of a factorization it couldn't finish, because neither
+/// [pollard_rho] nor [cfrac_factor] could split one of the composite factors. Implements
+/// [std::error::Error].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartialFactorization {
+    /// The prime factors found, sorted and merged as [factorize] returns them.
+    pub factors: Vec<(BigInt, u32)>,
+    /// The composite rest of `n`, which is `n` divided by the product of [Self::factors].
+    pub unfactored: BigInt,
+}
+
+impl fmt::Display for PartialFactorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "couldn't split the composite factor {}", self.unfactored)
+    }
+}
+
+impl std::error::Error for PartialFactorization {}
+
+/// Factors `n` completely, returning each prime factor once with its exponent, sorted by the primes
+/// in increasing order.
+///
+/// Small factors are found by trial division by [FIRST_100_PRIMES], the rest are split with
+/// [pollard_rho], falling back to [cfrac_factor] when rho fails. Factors are checked for primality
+/// with [MillerRabinOracle], see [factorize_with_oracle] to use another test.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{factorization::*, BigInt};
+/// let factors = factorize(&360.into());
+/// assert_eq!(factors, [(2, 3), (3, 2), (5, 1)].map(|(p, e)| (BigInt::from(p), e)));
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+/// - a composite factor can't be split, see [factorize_checked]
+pub fn factorize(n: &BigInt) -> Vec<(BigInt, u32)> {
+    factorize_with(n, |_, _| {})
+}
+
+/// [factorize], but a composite factor that can't be split gives the factors found so far instead
+/// of a panic.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// assert_eq!(factorize_checked(&360.into()), Ok(factorize(&360.into())));
+/// ```
+///
+/// # Errors
+/// - [PartialFactorization] if neither [pollard_rho] nor [cfrac_factor] splits a composite factor
+///
+/// # Panics
+/// - `n < 1`
+pub fn factorize_checked(n: &BigInt) -> Result<Vec<(BigInt, u32)>, PartialFactorization> {
+    factorize_inner(n, &MillerRabinOracle, split_sequentially, |_, _| {})
+}
+
+/// The same as [factorize], but calls `on_factor` with each prime factor and its exponent as soon
+/// as the prime is found, so long-running callers can show progress.
+///
+/// Each prime is reported exactly once. Small primes are reported in increasing order, the large
+/// ones in whatever order they split off.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{factorization::*, BigInt};
+/// let mut found = vec![];
+/// let factors = factorize_with(&360.into(), |p, e| found.push((p.clone(), e)));
+/// assert_eq!(found, factors);
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+/// - a composite factor can't be split, see [factorize_checked]
+pub fn factorize_with(n: &BigInt, on_factor: impl FnMut(&BigInt, u32)) -> Vec<(BigInt, u32)> {
+    factorize_inner(n, &MillerRabinOracle, split_sequentially, on_factor)
+        .unwrap_or_else(|partial| panic!("{}", partial))
+}
+
+/// The same as [factorize], but asks `oracle` which of the factors are prime instead of using
+/// [MillerRabinOracle].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{factorization::*, primality::*, BigInt};
+/// let factors = factorize_with_oracle(&360.into(), &MillerRabinOracle);
+/// assert_eq!(factors, factorize(&360.into()));
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+/// - `oracle` calls a prime composite, as it can't be split
+/// - a composite factor can't be split, see [factorize_checked]
+pub fn factorize_with_oracle(n: &BigInt, oracle: &impl PrimalityOracle) -> Vec<(BigInt, u32)> {
+    factorize_inner(n, oracle, split_sequentially, |_, _| {})
+        .unwrap_or_else(|partial| panic!("{}", partial))
+}
+
+/// The same as [factorize], but each composite is split by racing [pollard_rho],
+/// [pollard_p_minus_one] and [cfrac_factor] on rayon's thread pool. The first factor found wins,
+/// and the other methods are told to stop.
+///
+/// Which method wins can vary from run to run, but the factorization is always the same.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// let n = 1_000_003u64 * 1_000_033 * 1_000_037;
+/// assert_eq!(factorize_racing(&n.into()), factorize(&n.into()));
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+/// - none of the methods can split a composite factor
+#[cfg(feature = "parallel")]
+pub fn factorize_racing(n: &BigInt) -> Vec<(BigInt, u32)> {
+    factorize_inner(n, &MillerRabinOracle, split_racing, |_, _| {})
+        .unwrap_or_else(|partial| panic!("{}", partial))
+}
+
+/// Splits a composite with [pollard_rho], falling back to [cfrac_factor].
+fn split_sequentially(m: &BigInt) -> Option<BigInt> {
+    pollard_rho(m).or_else(|| cfrac_factor(m, CFRAC_MAX_ITERS))
+}
+
+/// Splits a composite with whichever of the methods in [factorize_racing] finishes first.
+#[cfg(feature = "parallel")]
+fn split_racing(m: &BigInt) -> Option<BigInt> {
+    use std::sync::OnceLock;
+
+    let cancelled = AtomicBool::new(false);
+    let winner = OnceLock::new();
+    let finish = |factor: Option<BigInt>| {
+        if let Some(factor) = factor {
+            let _ = winner.set(factor);
+            cancelled.store(true, Ordering::Relaxed);
+        }
+    };
+    rayon::scope(|s| {
+        s.spawn(|_| finish(pollard_rho_cancellable(m, &cancelled)));
+        s.spawn(|_| finish(p_minus_one_cancellable(m, P_MINUS_ONE_BOUND, &cancelled)));
+        s.spawn(|_| finish(cfrac_cancellable(m, CFRAC_MAX_ITERS, &cancelled)));
+    });
+    winner.into_inner()
+}
+
+/// The number of continued fraction terms [factorize] lets [cfrac_factor] try.
+const CFRAC_MAX_ITERS: u32 = 1_000_000;
+
+/// The smoothness bound [factorize_racing] uses for [pollard_p_minus_one].
+#[cfg(feature = "parallel")]
+const P_MINUS_ONE_BOUND: u32 = 100_000;
+
+fn factorize_inner(
+    n: &BigInt,
+    oracle: &impl PrimalityOracle,
+    split: impl Fn(&BigInt) -> Option<BigInt>,
+    mut on_factor: impl FnMut(&BigInt, u32),
+) -> Result<Vec<(BigInt, u32)>, PartialFactorization> {
+    assert!(n.is_positive());
+
+    let mut factors: Vec<(BigInt, u32)> = vec![];
+    let mut rest = n.clone();
+
+    for p in FIRST_100_PRIMES {
+        let mut exponent = 0;
+        while rest.is_divisible_u(*p) {
+            rest /= *p;
+            exponent += 1;
+        }
+        if exponent > 0 {
+            let p = BigInt::from(*p);
+            on_factor(&p, exponent);
+            factors.push((p, exponent));
+        }
+    }
+
+    let mut composites = vec![];
+    if rest > 1 {
+        composites.push(rest);
+    }
+
+    while let Some(m) = composites.pop() {
+        if m == 1 {
+            continue;
+        }
+
+        if oracle.is_prime(&m) != Primality::Composite {
+            // Other copies of m may still be waiting to be split off, take them all out now so m
+            // is reported once with its full exponent.
+            let mut exponent = 1;
+            for c in composites.iter_mut() {
+                exponent += c.remove_factor_mut(&m);
+            }
+            on_factor(&m, exponent);
+            factors.push((m, exponent));
+            continue;
+        }
+
+        let Some(factor) = split(&m) else {
+            let unfactored = composites.into_iter().fold(m, |rest, c| rest * c);
+            return Err(PartialFactorization {
+                factors: merge_factors(factors),
+                unfactored,
+            });
+        };
+        composites.push(BigInt::from(&m / &factor));
+        composites.push(factor);
+    }
+
+    Ok(merge_factors(factors))
+}
+
+/// Sorts `factors` by the primes and adds up the exponents of any prime that appears twice.
+fn merge_factors(mut factors: Vec<(BigInt, u32)>) -> Vec<(BigInt, u32)> {
+    // Each prime should only have been found once, but merging keeps the output canonical even if
+    // the splitting in factorize_inner changes.
+    factors.sort();
+    factors.dedup_by(|(p, e), (last, last_e)| {
+        let same = p == last;
+        if same {
+            *last_e += *e;
+        }
+        same
+    });
+    factors
+}
+
+/// Factors `n!` without computing it, returning each prime `p <= n` with its exponent, in
+/// increasing order.
+///
+/// By Legendre's formula the exponent of `p` in `n!` is `n/p + n/p**2 + n/p**3 + ...`, rounding
+/// each term down: `n/p` of the factors are multiples of `p`, `n/p**2` of those have a second
+/// factor `p`, and so on.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// // 10! = 3628800 = 2**8 3**4 5**2 7
+/// assert_eq!(factorial_factorization(10), [(2, 8), (3, 4), (5, 2), (7, 1)]);
+/// ```
+///
+/// # Panics
+/// - The sieve up to `n` can't be allocated.
+pub fn factorial_factorization(n: u64) -> Vec<(u64, u64)> {
+    // 0! = 1! = 1 has no prime factors.
+    if n < 2 {
+        return vec![];
+    }
+
+    esieve::sieve_u64(n)
+        .unwrap_or_else(|e| panic!("can't sieve the primes up to {}: {}", n, e))
+        .into_iter()
+        .map(|p| {
+            let mut exponent = 0;
+            let mut multiples = n;
+            while multiples > 0 {
+                multiples /= p;
+                exponent += multiples;
+            }
+            (p, exponent)
+        })
+        .collect()
+}
+
+/// Checks whether `n` is `bound`-smooth, i.e. none of its prime factors exceed `bound`.
+///
+/// The primes up to `bound` are sieved and divided out of `n`, and `n` is smooth if nothing is
+/// left. Smooth numbers are what [cfrac_factor] collects relations from.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// assert!(is_smooth(&360.into(), 5));
+/// assert!(!is_smooth(&14.into(), 3));
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+/// - The sieve up to `bound` can't be allocated.
+pub fn is_smooth(n: &BigInt, bound: usize) -> bool {
+    assert!(n.is_positive());
+
+    if bound < 2 {
+        return *n == 1;
+    }
+
+    let mut rest = n.clone();
+    for p in esieve::sieve(bound).expect("the bound is at least 2") {
+        if rest == 1 {
+            break;
+        }
+        rest.remove_factor_mut(&BigInt::from(p));
+    }
+    rest == 1
+}
+
+/// Formats the factorization of `n` the way it would be written by hand, such as `2^3 * 5 * 7^2`.
+/// Exponents of 1 are left out, and 1 itself is written `1`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// assert_eq!(factorization_string(&1960.into()), "2^3 * 5 * 7^2");
+/// assert_eq!(factorization_string(&97.into()), "97");
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+pub fn factorization_string(n: &BigInt) -> String {
+    let factors = factorize(n);
+    if factors.is_empty() {
+        return "1".to_string();
+    }
+
+    factors
+        .iter()
+        .map(|(p, e)| match e {
+            1 => p.to_string(),
+            _ => format!("{}^{}", p, e),
+        })
+        .collect::<Vec<_>>()
+        .join(" * ")
+}
+
+/// The prime signature of `n`: the exponents of its factorization, sorted in increasing order. For
+/// example `12 = 2**2 3` has the signature `[1, 2]`.
+///
+/// Numbers with the same signature have the same multiplicative shape, so they agree on things
+/// like the number of divisors. The signature of 1 is empty.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// assert_eq!(prime_signature(&12.into()), [1, 2]);
+/// assert_eq!(prime_signature(&360.into()), [1, 2, 3]);
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+pub fn prime_signature(n: &BigInt) -> Vec<u32> {
+    let mut signature: Vec<u32> = factorize(n).into_iter().map(|(_, e)| e).collect();
+    signature.sort_unstable();
+    signature
+}
+
+/// Whether `a` and `b` have the same [prime_signature], such as 12 and 18, which are both a prime
+/// times the square of another.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// assert!(same_signature(&12.into(), &18.into()));
+/// assert!(!same_signature(&12.into(), &16.into()));
+/// ```
+///
+/// # Panics
+/// - `a < 1` or `b < 1`
+pub fn same_signature(a: &BigInt, b: &BigInt) -> bool {
+    prime_signature(a) == prime_signature(b)
+}
+
+/// Checks a factorization obtained elsewhere: the product of `p**e` over `factors` must be `n`, and
+/// each `p` must pass 25 rounds of [miller_rabin_rounds].
+///
+/// The factors don't have to be sorted or distinct. Primality is only checked probabilistically, so
+/// a composite factor slips through with probability at most `4**-25`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{factorization::*, BigInt};
+/// let factors = [(2, 3), (3, 2), (5, 1)].map(|(p, e)| (BigInt::from(p), e));
+/// assert!(verify_factorization(&360.into(), &factors));
+/// assert!(!verify_factorization(&720.into(), &factors));
+/// ```
+pub fn verify_factorization(n: &BigInt, factors: &[(BigInt, u32)]) -> bool {
+    let product = factors
+        .iter()
+        .fold(BigInt::from(1), |product, (p, e)| product * BigInt::from(p.pow(*e)));
+    if product != *n {
+        return false;
+    }
+
+    let rounds = NonZeroU32::new(25).unwrap();
+    factors.iter().all(|(p, _)| miller_rabin_rounds(p, rounds))
+}
+
+/// Multiplies `factors` together, for building composites like `53 * 59` without overflowing.
+/// The empty product is 1.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// assert_eq!(product_of(&[53, 59]), 3127);
+/// assert_eq!(product_of(&[]), 1);
+/// ```
+pub fn product_of(factors: &[u32]) -> BigInt {
+    factors
+        .iter()
+        .fold(BigInt::from(1), |product, factor| product * *factor)
+}
+
+/// How many polynomials [pollard_rho] tries before giving up.
+const POLLARD_RHO_ATTEMPTS: u32 = 20;
+
+/// Pollard's rho method for finding a factor of `n`.
+///
+/// Iterates `x -> x**2 + c mod n`. Modulo an unknown prime factor `p` the sequence starts cycling
+/// after about `sqrt(p)` steps, which Floyd's cycle detection notices as `gcd(x - y, n) > 1`.
+///
+/// Sometimes the cycle closes modulo `n` itself before any factor shows, as for `n = 25` with
+/// `c = 1`. Then the sequence is started over with the next `c` and a new starting point, for up
+/// to 20 attempts: `c = 1, 2, 3, ...` starting from `x = 2, 3, 4, ...`.
+///
+/// Returns a nontrivial factor, or `None` if `n` is prime, less than 4, or every attempt failed.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// assert_eq!(pollard_rho(&8051.into()), Some(97.into()));
+/// assert_eq!(pollard_rho(&25.into()), Some(5.into()));
+/// ```
+pub fn pollard_rho(n: &BigInt) -> Option<BigInt> {
+    pollard_rho_cancellable(n, &AtomicBool::new(false))
+}
+
+/// [pollard_rho], but giving up with `None` once `cancelled` is set.
+fn pollard_rho_cancellable(n: &BigInt, cancelled: &AtomicBool) -> Option<BigInt> {
+    if *n < 4 || n.is_probably_prime(30) != IsPrime::No {
+        return None;
+    }
+    if n.is_even() {
+        return Some(BigInt::from(2));
+    }
+
+    (1..=POLLARD_RHO_ATTEMPTS)
+        .find_map(|c| pollard_rho_attempt(n, c, BigInt::from(c + 1), cancelled))
+}
+
+/// One run of [pollard_rho] with the polynomial `x**2 + c` starting from `start`. Returns `None`
+/// if the cycle closed modulo `n` itself, or `cancelled` was set.
+fn pollard_rho_attempt(
+    n: &BigInt,
+    c: u32,
+    start: BigInt,
+    cancelled: &AtomicBool,
+) -> Option<BigInt> {
+    let step = |x: &BigInt| {
+        let mut next = BigInt::from(x.square_ref());
+        next += c;
+        next.modulo(n)
+    };
+
+    let mut x = start.clone();
+    let mut y = start;
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+        x = step(&x);
+        y = step(&step(&y));
+        let d = BigInt::from(&x - &y).gcd(n);
+        if d == *n {
+            return None;
+        }
+        if d != 1 {
+            return Some(d);
+        }
+    }
+}
+
+/// Pollard's `p - 1` method for finding a factor of `n`.
+///
+/// For a prime `p | n`, Fermat's little theorem gives `a**M = 1 mod p` whenever `p - 1 | M`. With
+/// `M` the product of the prime powers up to `bound`, that holds if `p - 1` is `bound`-smooth, and
+/// then `gcd(a**M - 1, n)` is divisible by `p`. The gcd is checked after each prime power, with
+/// `a = 2`.
+///
+/// Returns a nontrivial factor, or `None` if `n` is prime, less than 4, no prime factor has a
+/// smooth enough `p - 1`, or all of them turned up at once.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// // 1000033 - 1 = 2**5 * 3 * 11 * 947, but 1000003 - 1 = 2 * 3 * 166667.
+/// let n = 1_000_003u64 * 1_000_033;
+/// assert_eq!(pollard_p_minus_one(&n.into(), 1000), Some(1_000_033.into()));
+/// assert_eq!(pollard_p_minus_one(&n.into(), 100), None);
+/// ```
+///
+/// # Panics
+/// - The sieve up to `bound` can't be allocated.
+pub fn pollard_p_minus_one(n: &BigInt, bound: u32) -> Option<BigInt> {
+    p_minus_one_cancellable(n, bound, &AtomicBool::new(false))
+}
+
+/// [pollard_p_minus_one], but giving up with `None` once `cancelled` is set.
+fn p_minus_one_cancellable(n: &BigInt, bound: u32, cancelled: &AtomicBool) -> Option<BigInt> {
+    if *n < 4 || n.is_probably_prime(30) != IsPrime::No {
+        return None;
+    }
+    if n.is_even() {
+        return Some(BigInt::from(2));
+    }
+
+    // Below 2 there are no prime powers, so no M to try.
+    if bound < 2 {
+        return None;
+    }
+
+    let bound = bound as u64;
+    let primes = esieve::sieve_u64(bound)
+        .unwrap_or_else(|e| panic!("can't sieve the primes up to {}: {}", bound, e));
+    let mut a = BigInt::from(2);
+    for q in primes {
+        if cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        // The largest power of q up to the bound.
+        let mut power = q;
+        while power * q <= bound {
+            power *= q;
+        }
+        a.pow_mod_mut(&BigInt::from(power), n)
+            .expect("the exponent is positive");
+
+        let d = BigInt::from(&a - 1u32).gcd(n);
+        if d == *n {
+            return None;
+        }
+        if d != 1 {
+            return Some(d);
+        }
+    }
+    None
+}
+
+/// Memoizes [factorize] for the most recently used numbers.
+///
+/// When full, the least recently used entry is evicted to make room.
+///
+/// # Example
+///
+/// ```
+/// use std::num::NonZeroUsize;
+/// use fermats_kitchen::factorization::*;
+///
+/// let mut cache = FactorCache::with_capacity(NonZeroUsize::new(16).unwrap());
+/// let first = cache.factorize(&1001.into());
+/// assert_eq!(cache.factorize(&1001.into()), first);
+/// assert_eq!(cache.hits(), 1);
+/// ```
+pub struct FactorCache {
+    capacity: NonZeroUsize,
+    factorizations: HashMap<BigInt, Vec<(BigInt, u32)>>,
+    /// The cached numbers, least recently used first.
+    recency: VecDeque<BigInt>,
+    hits: u64,
+    misses: u64,
+}
+
+impl FactorCache {
+    pub fn with_capacity(capacity: NonZeroUsize) -> Self {
+        Self {
+            capacity,
+            factorizations: HashMap::new(),
+            recency: VecDeque::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The same as [factorize], but reuses the result if `n` was factored recently.
+    pub fn factorize(&mut self, n: &BigInt) -> Vec<(BigInt, u32)> {
+        if let Some(factors) = self.factorizations.get(n) {
+            self.hits += 1;
+            let position = self
+                .recency
+                .iter()
+                .position(|m| m == n)
+                .expect("cached numbers are tracked");
+            let m = self.recency.remove(position).unwrap();
+            self.recency.push_back(m);
+            return factors.clone();
+        }
+
+        self.misses += 1;
+        let factors = factorize(n);
+        if self.recency.len() == self.capacity.get() {
+            let evicted = self.recency.pop_front().unwrap();
+            self.factorizations.remove(&evicted);
+        }
+        self.recency.push_back(n.clone());
+        self.factorizations.insert(n.clone(), factors.clone());
+        factors
+    }
+
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.capacity
+    }
+
+    /// The number of cached factorizations.
+    pub fn len(&self) -> usize {
+        self.recency.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recency.is_empty()
+    }
+
+    /// How many calls were answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// How many calls had to factor the number.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+/// Continued fraction factorization (CFRAC), by Morrison and Brillhart.
+///
+/// *This is experimental, and bounded by `max_iters`: it gives up and returns `None` after that
+/// many terms of the continued fraction.*
+///
+/// The convergents `A/B` of the continued fraction of `sqrt(n)` satisfy
+/// ```text
+///     A**2 = (-1)**(i+1) Q   mod n
+/// ```
+/// where `Q < 2 sqrt(n)` is small. The `Q` that factor completely over a base of small primes are
+/// kept, and once there are more of them than primes in the base, some subset multiplies to a
+/// square `Y**2`. With `X` the product of the corresponding `A`, `X**2 = Y**2 mod n`, and
+/// `gcd(X - Y, n)` is a factor of `n` about half of the time.
+///
+/// Returns a nontrivial factor of `n`, or `None` if `n` is prime, less than 4, or no factor turned
+/// up within `max_iters` terms. Practical up to about 20 digits.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// let n = 1000003u64 * 1000033;
+/// let factor = cfrac_factor(&n.into(), 10_000).unwrap();
+/// assert!(factor == 1000003 || factor == 1000033);
+/// ```
+pub fn cfrac_factor(n: &BigInt, max_iters: u32) -> Option<BigInt> {
+    cfrac_cancellable(n, max_iters, &AtomicBool::new(false))
+}
+
+/// [cfrac_factor], but giving up with `None` once `cancelled` is set.
+fn cfrac_cancellable(n: &BigInt, max_iters: u32, cancelled: &AtomicBool) -> Option<BigInt> {
+    if *n < 4 || n.is_probably_prime(30) != IsPrime::No {
+        return None;
+    }
+    if n.is_even() {
+        return Some(BigInt::from(2));
+    }
+
+    let (a0, remainder): (BigInt, BigInt) = n.sqrt_rem_ref().into();
+    if remainder == 0 {
+        return Some(a0);
+    }
+
+    let bound = smoothness_bound(n).clamp(50.0, 1e6) as usize;
+    let factor_base = match checked_factor_base(n, bound) {
+        Ok(factor_base) => factor_base,
+        Err(factor) => return Some(factor),
+    };
+    // One column for the sign, then one for each prime.
+    let columns = factor_base.len() + 1;
+
+    let mut relations: Vec<(BigInt, Vec<u32>)> = vec![];
+    let mut rows: Vec<BitVector> = vec![];
+
+    let mut m = BigInt::new();
+    let mut d = BigInt::from(1);
+    let mut a = a0.clone();
+    let mut numerator_prev = BigInt::from(1);
+    let mut numerator = BigInt::from(&a0 % n);
+
+    for i in 0..max_iters {
+        if cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+        m = BigInt::from(&d * &a) - m;
+        d = BigInt::from(n - m.square_ref()) / &d;
+        a = BigInt::from(&a0 + &m) / &d;
+
+        if let Some(mut exponents) = factor_over_base(&d, &factor_base) {
+            // The sign is (-1)**(i+1), stored as the exponent of -1 in front of the primes.
+            exponents.insert(0, if i % 2 == 0 { 1 } else { 0 });
+
+            let mut row = BitVector::zeros(columns);
+            for (column, exponent) in exponents.iter().enumerate() {
+                if exponent % 2 == 1 {
+                    row.flip(column);
+                }
+            }
+            rows.push(row);
+            relations.push((numerator.clone(), exponents));
+
+            if rows.len() > columns && rows.len().is_multiple_of(8) {
+                if let Some(factor) = combine_relations(n, &factor_base, &relations, &rows) {
+                    return Some(factor);
+                }
+            }
+        }
+
+        let next = BigInt::from(&a * &numerator) + &numerator_prev;
+        numerator_prev = numerator;
+        numerator = next.modulo(n);
+    }
+
+    combine_relations(n, &factor_base, &relations, &rows)
+}
+
+/// The factor base for sieving methods like the quadratic sieve: the primes `p <= bound` modulo
+/// which `n` is a square, so that the Legendre symbol `(n/p)` isn't -1.
+///
+/// Only these primes can divide the values such methods try to factor, `x**2 - n` for the
+/// quadratic sieve. 2 is always included, as is any `p | n`, where `(n/p) = 0`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// // 10 = 1**2 mod 3, 0 mod 5 and 6**2 mod 13, but isn't a square mod 7 or 11.
+/// assert_eq!(factor_base(&10.into(), 13), [2, 3, 5, 13]);
+/// ```
+///
+/// # Panics
+/// - The sieve up to `bound` can't be allocated.
+pub fn factor_base(n: &BigInt, bound: usize) -> Vec<usize> {
+    // Below 2 there are no primes.
+    if bound < 2 {
+        return vec![];
+    }
+
+    esieve::sieve(bound)
+        .unwrap_or_else(|e| panic!("can't sieve the primes up to {}: {}", bound, e))
+        .into_iter()
+        .filter(|p| *p == 2 || n.legendre(&BigInt::from(*p)) != -1)
+        .collect()
+}
+
+/// `exp(sqrt(ln n ln ln n) / 2)`, about the optimal bound on the factor base for CFRAC and the
+/// quadratic sieve.
+fn smoothness_bound(n: &BigInt) -> f64 {
+    let ln_n = n.significant_bits() as f64 * std::f64::consts::LN_2;
+    (0.5 * (ln_n * ln_n.ln()).sqrt()).exp()
+}
+
+/// The [factor_base] for CFRAC and the quadratic sieve. Only these primes can divide the `Q` of
+/// the continued fraction or the `x**2 - n` of the sieve. If one of them divides `n`, it is
+/// returned as the error instead.
+fn checked_factor_base(n: &BigInt, bound: usize) -> Result<Vec<usize>, BigInt> {
+    let factor_base = factor_base(n, bound);
+    match factor_base.iter().find(|p| n.is_divisible(&BigInt::from(**p))) {
+        Some(p) => Err(BigInt::from(*p)),
+        None => Ok(factor_base),
+    }
+}
+
+/// How many consecutive `x` [quadratic_sieve] sieves at a time.
+const QS_INTERVAL: usize = 1 << 16;
+
+/// How many intervals [quadratic_sieve] sieves before giving up.
+const QS_MAX_INTERVALS: usize = 1000;
+
+/// The quadratic sieve, by Pomerance.
+///
+/// *This is a basic version without multiple polynomials, practical for `n` of about 60 to 80
+/// bits. It gives up and returns `None` after sieving a fixed number of intervals.*
+///
+/// Like [cfrac_factor] it looks for `x**2 = y**2 mod n`, which splits `n` through `gcd(x - y, n)`
+/// about half of the time. Here the relations come from `Q(x) = x**2 - n` for `x` just above
+/// `sqrt(n)`, which is small and `x**2 mod n`. A prime `p` divides `Q(x)` exactly when
+/// `x = +-sqrt(n) mod p`, so instead of trial dividing each `Q(x)`, each prime in the
+/// [factor_base] adds its logarithm at those `x` in a whole interval. Only the `x` whose sums come
+/// close to `log Q(x)` are likely smooth, and those are factored to get the relations.
+///
+/// Returns a nontrivial factor of `n`, or `None` if `n` is prime, less than 4, or no factor turned
+/// up in time.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::factorization::*;
+/// let n = 1000003u64 * 1000033;
+/// let factor = quadratic_sieve(&n.into()).unwrap();
+/// assert!(factor == 1000003 || factor == 1000033);
+/// ```
+pub fn quadratic_sieve(n: &BigInt) -> Option<BigInt> {
+    if *n < 4 || n.is_probably_prime(30) != IsPrime::No {
+        return None;
+    }
+    if n.is_even() {
+        return Some(BigInt::from(2));
+    }
+
+    let (root, remainder): (BigInt, BigInt) = n.sqrt_rem_ref().into();
+    if remainder == 0 {
+        return Some(root);
+    }
+
+    // The asymptotic bound is too small for the sizes this is meant for, and a larger base finds
+    // smooth values much sooner than it adds columns.
+    let bound = (3.0 * smoothness_bound(n)).clamp(500.0, 1e6) as usize;
+    let factor_base = match checked_factor_base(n, bound) {
+        Ok(factor_base) => factor_base,
+        Err(factor) => return Some(factor),
+    };
+    // One column for the sign like in CFRAC, though Q(x) is always positive here.
+    let columns = factor_base.len() + 1;
+
+    // The x mod p where p | Q(x). There is one for p = 2 and two for the odd primes, as n is a
+    // nonzero square mod each of them.
+    let roots: Vec<Vec<usize>> = factor_base
+        .iter()
+        .map(|p| {
+            let root = sqrt_mod_prime(n.mod_u(*p as u32) as u64, *p as u64) as usize;
+            if root == p - root {
+                vec![root]
+            } else {
+                vec![root, p - root]
+            }
+        })
+        .collect();
+    let logs: Vec<f32> = factor_base.iter().map(|p| (*p as f32).log2()).collect();
+    // Prime powers and 2 aren't sieved properly, so smooth values can fall short by a few primes.
+    let slack = 2.0 * logs.last().copied().unwrap_or(1.0);
+
+    let mut relations: Vec<(BigInt, Vec<u32>)> = vec![];
+    let mut rows: Vec<BitVector> = vec![];
+    let mut sums = vec![0f32; QS_INTERVAL];
+    let mut start: BigInt = root + 1;
+
+    for _ in 0..QS_MAX_INTERVALS {
+        sums.fill(0.0);
+        for ((p, roots), log) in factor_base.iter().zip(&roots).zip(&logs) {
+            let offset = start.mod_u(*p as u32) as usize;
+            for root in roots {
+                for sum in sums.iter_mut().skip((root + p - offset) % p).step_by(*p) {
+                    *sum += log;
+                }
+            }
+        }
+
+        // Q grows across the interval, so its end gives a threshold that is a little too high at
+        // the start. That only loses a few relations.
+        let end = BigInt::from(&start + QS_INTERVAL);
+        let q_end = BigInt::from(end.square_ref()) - n;
+        let threshold = q_end.significant_bits() as f32 - slack;
+        for (i, _) in sums.iter().enumerate().filter(|(_, sum)| **sum >= threshold) {
+            let x = BigInt::from(&start + i);
+            let q = BigInt::from(x.square_ref()) - n;
+            if let Some(mut exponents) = factor_over_base(&q, &factor_base) {
+                exponents.insert(0, 0);
+
+                let mut row = BitVector::zeros(columns);
+                for (column, exponent) in exponents.iter().enumerate() {
+                    if exponent % 2 == 1 {
+                        row.flip(column);
+                    }
+                }
+                rows.push(row);
+                relations.push((x.modulo(n), exponents));
+            }
+        }
+
+        if rows.len() > columns {
+            if let Some(factor) = combine_relations(n, &factor_base, &relations, &rows) {
+                return Some(factor);
+            }
+        }
+        start = end;
+    }
+    None
+}
+
+/// A square root of `a` modulo the prime `p`, by the Tonelli-Shanks algorithm. `a` must be a
+/// square mod `p`.
+fn sqrt_mod_prime(a: u64, p: u64) -> u64 {
+    let a = a % p;
+    if p == 2 || a == 0 {
+        return a;
+    }
+
+    // p - 1 = q 2**s with q odd.
+    let s = (p - 1).trailing_zeros();
+    let q = (p - 1) >> s;
+    if s == 1 {
+        return mod_pow_u64(a, (p + 1) / 4, p);
+    }
+
+    let non_residue = (2..p)
+        .find(|z| mod_pow_u64(*z, (p - 1) / 2, p) == p - 1)
+        .expect("half of the residues are non-residues");
+    let mut m = s;
+    let mut c = mod_pow_u64(non_residue, q, p);
+    let mut t = mod_pow_u64(a, q, p);
+    let mut root = mod_pow_u64(a, q.div_ceil(2), p);
+    while t != 1 {
+        // The least i with t**(2**i) = 1, which is below m as a is a square.
+        let mut i = 0;
+        let mut t_power = t;
+        while t_power != 1 {
+            t_power = t_power * t_power % p;
+            i += 1;
+        }
+        let b = mod_pow_u64(c, 1 << (m - i - 1), p);
+        m = i;
+        c = b * b % p;
+        t = t * c % p;
+        root = root * b % p;
+    }
+    root
+}
+
+/// The exponents of each prime in the factor base if `value` factors completely over it.
+fn factor_over_base(value: &BigInt, factor_base: &[usize]) -> Option<Vec<u32>> {
+    let mut rest = value.clone();
+    let mut exponents = vec![0; factor_base.len()];
+    for (exponent, p) in exponents.iter_mut().zip(factor_base) {
+        let p = BigInt::from(*p);
+        while rest.is_divisible(&p) {
+            rest /= &p;
+            *exponent += 1;
+        }
+    }
+    (rest == 1).then_some(exponents)
+}
+
+/// Tries to turn the relations into a congruence of squares that splits `n`.
+fn combine_relations(
+    n: &BigInt,
+    factor_base: &[usize],
+    relations: &[(BigInt, Vec<u32>)],
+    rows: &[BitVector],
+) -> Option<BigInt> {
+    for dependency in gf2::find_dependencies(rows, factor_base.len() + 1) {
+        let mut x = BigInt::from(1);
+        let mut exponent_sums = vec![0u32; factor_base.len() + 1];
+        for i in &dependency {
+            let (numerator, exponents) = &relations[*i];
+            x = (x * numerator).modulo(n);
+            for (sum, exponent) in exponent_sums.iter_mut().zip(exponents) {
+                *sum += exponent;
+            }
+        }
+
+        // The sign exponent is even, so only the primes contribute to the square root.
+        let mut y = BigInt::from(1);
+        for (p, sum) in factor_base.iter().zip(&exponent_sums[1..]) {
+            let power = BigInt::from(*p)
+                .pow_mod(&BigInt::from(sum / 2), n)
+                .expect("exponent is non-negative");
+            y = (y * power).modulo(n);
+        }
+
+        let factor = BigInt::from(x - y).gcd(n);
+        if factor != 1 && factor != *n {
+            return Some(factor);
+        }
+    }
+    None
+}
@@ -0,0 +1,459 @@
+use rug::ops::Pow;
+
+use super::*;
+
+fn factors(pairs: &[(u64, u32)]) -> Vec<(BigInt, u32)> {
+    pairs.iter().map(|(p, e)| (BigInt::from(*p), *e)).collect()
+}
+
+#[test]
+fn test_factorize() {
+    assert_eq!(factorize(&BigInt::from(1)), factors(&[]));
+    assert_eq!(factorize(&BigInt::from(2)), factors(&[(2, 1)]));
+    assert_eq!(factorize(&BigInt::from(360)), factors(&[(2, 3), (3, 2), (5, 1)]));
+    assert_eq!(factorize(&BigInt::from(541 * 541)), factors(&[(541, 2)]));
+    assert_eq!(
+        factorize(&BigInt::from(1_000_000_007u64 * 1_000_000_009)),
+        factors(&[(1_000_000_007, 1), (1_000_000_009, 1)])
+    );
+    // 2**64 + 1 = 274177 * 67280421310721
+    let n = (BigInt::from(1) << 64) + 1;
+    assert_eq!(
+        factorize(&n),
+        factors(&[(274177, 1), (67280421310721, 1)])
+    );
+
+    for n in 1..2000u32 {
+        let product = factorize(&BigInt::from(n))
+            .iter()
+            .fold(BigInt::from(1), |acc, (p, e)| acc * BigInt::from(p.pow(*e)));
+        assert_eq!(product, n);
+    }
+}
+
+#[test]
+fn test_factorize_is_sorted_and_merged() {
+    assert_eq!(
+        factorize(&BigInt::from(32 * 9 * 101)),
+        factors(&[(2, 5), (3, 2), (101, 1)])
+    );
+
+    // Primes beyond the trial division, which rho splits off in no particular order.
+    let n = BigInt::from(1_000_000_009u64).pow(2)
+        * BigInt::from(1_000_003u64)
+        * BigInt::from(1_000_000_007u64).pow(3)
+        * 32;
+    assert_eq!(
+        factorize(&n),
+        factors(&[
+            (2, 5),
+            (1_000_003, 1),
+            (1_000_000_007, 3),
+            (1_000_000_009, 2)
+        ])
+    );
+}
+
+#[test]
+#[should_panic]
+fn test_factorize_zero() {
+    factorize(&BigInt::from(0));
+}
+
+#[test]
+fn test_factorize_with() {
+    let p = BigInt::from(1_000_000_007u64);
+    let q = BigInt::from(1_000_000_009u64);
+    let inputs = [
+        BigInt::from(1),
+        BigInt::from(360),
+        BigInt::from(541 * 541),
+        // Large repeated primes, which pollard_rho splits off one copy at a time.
+        BigInt::from(&p * &p) * &q * 12,
+        BigInt::from((&p).pow(3)) * BigInt::from((&q).pow(2)),
+        (BigInt::from(1) << 64) + 1,
+    ];
+
+    for n in inputs {
+        let mut found = vec![];
+        let factors = factorize_with(&n, |f, e| found.push((f.clone(), e)));
+        assert_eq!(factors, factorize(&n));
+
+        found.sort();
+        assert_eq!(found, factors);
+    }
+}
+
+#[test]
+fn test_factorize_with_oracle() {
+    let queried = std::cell::RefCell::new(vec![]);
+    let oracle = |n: &BigInt| {
+        queried.borrow_mut().push(n.clone());
+        MillerRabinOracle.is_prime(n)
+    };
+
+    let n = BigInt::from(1_000_000_007u64 * 1_000_000_009) * 12;
+    assert_eq!(factorize_with_oracle(&n, &oracle), factorize(&n));
+    // The small primes come from trial division, only what is left needs the oracle.
+    let mut queried = queried.into_inner();
+    queried.sort();
+    assert_eq!(
+        queried,
+        [1_000_000_007u64, 1_000_000_009, 1_000_000_007 * 1_000_000_009].map(BigInt::from)
+    );
+
+    // An oracle that calls every number prime stops at the first one it is asked about.
+    let gullible = |_: &BigInt| Primality::ProbablyPrime;
+    let n = BigInt::from(1_000_003u64 * 1_000_033);
+    assert_eq!(factorize_with_oracle(&n, &gullible), vec![(n, 1)]);
+}
+
+#[test]
+fn test_factorize_checked() {
+    let rest = BigInt::from(1_000_003u64 * 1_000_033);
+    let n = BigInt::from(&rest * 12);
+    assert_eq!(factorize_checked(&n), Ok(factorize(&n)));
+
+    // Trial division still finds the small primes when nothing else can be split.
+    let partial = factorize_inner(&n, &MillerRabinOracle, |_| None, |_, _| {}).unwrap_err();
+    assert_eq!(partial.factors, factors(&[(2, 2), (3, 1)]));
+    assert_eq!(partial.unfactored, rest);
+
+    // An oracle that calls every number composite leaves both halves unsplit.
+    let sceptic = |_: &BigInt| Primality::Composite;
+    let split_once = |m: &BigInt| (*m == rest).then(|| BigInt::from(1_000_003));
+    let partial = factorize_inner(&n, &sceptic, split_once, |_, _| {}).unwrap_err();
+    assert_eq!(partial.factors, factors(&[(2, 2), (3, 1)]));
+    assert_eq!(partial.unfactored, rest);
+}
+
+#[test]
+fn test_factorial_factorization() {
+    assert_eq!(factorial_factorization(10), [(2, 8), (3, 4), (5, 2), (7, 1)]);
+    assert_eq!(factorial_factorization(0), Vec::<(u64, u64)>::new());
+    assert_eq!(factorial_factorization(1), Vec::<(u64, u64)>::new());
+    assert_eq!(factorial_factorization(2), [(2, 1)]);
+
+    // The same as factoring the factorial itself.
+    let mut factorial = BigInt::from(1);
+    for n in 2..60u64 {
+        factorial *= n;
+        let expected: Vec<(u64, u64)> = factorize(&factorial)
+            .into_iter()
+            .map(|(p, e)| (p.to_u64().unwrap(), u64::from(e)))
+            .collect();
+        assert_eq!(factorial_factorization(n), expected, "n = {}", n);
+    }
+
+    // 1000! ends in 249 zeros, limited by the 5s.
+    let factors = factorial_factorization(1000);
+    assert_eq!(factors.iter().find(|(p, _)| *p == 5), Some(&(5, 249)));
+    assert_eq!(factors.len(), 168);
+}
+
+#[test]
+fn test_factorization_string() {
+    assert_eq!(factorization_string(&BigInt::from(1)), "1");
+    assert_eq!(factorization_string(&BigInt::from(2)), "2");
+    assert_eq!(factorization_string(&BigInt::from(97)), "97");
+    assert_eq!(factorization_string(&BigInt::from(1024)), "2^10");
+    assert_eq!(factorization_string(&BigInt::from(360)), "2^3 * 3^2 * 5");
+    assert_eq!(factorization_string(&BigInt::from(2 * 2 * 2 * 5 * 7 * 7)), "2^3 * 5 * 7^2");
+    assert_eq!(factorization_string(&BigInt::from(561)), "3 * 11 * 17");
+    assert_eq!(
+        factorization_string(&((BigInt::from(1) << 64) + 1)),
+        "274177 * 67280421310721"
+    );
+}
+
+#[test]
+fn test_is_smooth() {
+    assert!(is_smooth(&BigInt::from(360), 5));
+    assert!(is_smooth(&BigInt::from(360), 6));
+    assert!(!is_smooth(&BigInt::from(360), 4));
+    assert!(!is_smooth(&BigInt::from(14), 3));
+    assert!(is_smooth(&BigInt::from(14), 7));
+    assert!(is_smooth(&BigInt::from(1), 0));
+    assert!(!is_smooth(&BigInt::from(2), 1));
+    assert!(is_smooth(&BigInt::from(1024), 2));
+
+    // The same answer as looking at the largest prime factor.
+    for n in 1..500u32 {
+        let largest = factorize(&BigInt::from(n)).last().map_or(1, |(p, _)| p.to_u32().unwrap());
+        for bound in [2, 3, 10, 23, 100] {
+            assert_eq!(
+                is_smooth(&BigInt::from(n), bound),
+                largest <= bound as u32,
+                "n = {}, bound = {}",
+                n,
+                bound
+            );
+        }
+    }
+
+    // 2**64 + 1 = 274177 * 67280421310721
+    let n = (BigInt::from(1) << 64) + 1;
+    assert!(!is_smooth(&n, 300_000));
+    assert!(is_smooth(&(BigInt::from(274177) * 1024u32), 300_000));
+}
+
+#[test]
+fn test_prime_signature() {
+    assert_eq!(prime_signature(&BigInt::from(1)), Vec::<u32>::new());
+    assert_eq!(prime_signature(&BigInt::from(97)), [1]);
+    assert_eq!(prime_signature(&BigInt::from(12)), [1, 2]);
+    assert_eq!(prime_signature(&BigInt::from(18)), [1, 2]);
+    assert_eq!(prime_signature(&BigInt::from(16)), [4]);
+    // 2**3 * 3 * 5**2, with the exponents out of order in the factorization.
+    assert_eq!(prime_signature(&BigInt::from(600)), [1, 2, 3]);
+
+    assert!(same_signature(&BigInt::from(12), &BigInt::from(18)));
+    assert!(!same_signature(&BigInt::from(12), &BigInt::from(16)));
+    assert!(same_signature(&BigInt::from(1), &BigInt::from(1)));
+    let (p, q) = (BigInt::from(1_000_000_007), BigInt::from(1_000_000_009));
+    assert!(same_signature(&BigInt::from(&p * &q), &BigInt::from(15)));
+    assert!(same_signature(&(BigInt::from(p.square_ref()) * &q), &BigInt::from(75)));
+    assert!(!same_signature(&p, &BigInt::from(1)));
+}
+
+#[test]
+fn test_verify_factorization() {
+    assert!(verify_factorization(&BigInt::from(360), &factors(&[(2, 3), (3, 2), (5, 1)])));
+    assert!(verify_factorization(&BigInt::from(360), &factors(&[(5, 1), (2, 3), (3, 1), (3, 1)])));
+    assert!(verify_factorization(&BigInt::from(1), &factors(&[])));
+    let n = BigInt::from(1_000_000_007u64 * 1_000_000_009);
+    assert!(verify_factorization(&n, &factorize(&n)));
+
+    // The product doesn't match.
+    assert!(!verify_factorization(&BigInt::from(360), &factors(&[(2, 3), (3, 2)])));
+    assert!(!verify_factorization(&BigInt::from(360), &factors(&[(2, 3), (3, 2), (7, 1)])));
+    assert!(!verify_factorization(&BigInt::from(2), &factors(&[])));
+
+    // The product matches, but a factor is composite or not a prime at all.
+    assert!(!verify_factorization(&BigInt::from(360), &factors(&[(4, 1), (2, 1), (9, 1), (5, 1)])));
+    assert!(!verify_factorization(&BigInt::from(561), &factors(&[(561, 1)])));
+    assert!(!verify_factorization(&BigInt::from(7), &factors(&[(7, 1), (1, 3)])));
+}
+
+#[test]
+fn test_factor_base() {
+    assert_eq!(factor_base(&BigInt::from(10), 13), [2, 3, 5, 13]);
+    assert_eq!(factor_base(&BigInt::from(10), 1), Vec::<usize>::new());
+
+    let n = BigInt::from(1_000_000_007u64 * 1_000_000_009);
+    let base = factor_base(&n, 1000);
+    assert!(base.len() > 50 && base.len() < 168);
+    for p in crate::esieve::sieve(1000).unwrap() {
+        // Whether n is a square mod p, by trying every residue.
+        let residue = BigInt::from(&n % p).to_usize().unwrap();
+        let is_square = (0..p).any(|x| x * x % p == residue);
+        assert_eq!(base.contains(&p), is_square, "p = {}", p);
+    }
+
+    // Divisors of n are squares too, as n = 0 mod p.
+    let n = BigInt::from(3 * 7 * 11 * 13);
+    assert_eq!(factor_base(&n, 13), [2, 3, 7, 11, 13]);
+}
+
+#[test]
+fn test_product_of() {
+    assert_eq!(product_of(&[53, 59]), 3127);
+    assert_eq!(product_of(&[]), 1);
+    assert_eq!(product_of(&[0, 7]), 0);
+    assert_eq!(product_of(&[u32::MAX; 3]), BigInt::from(u32::MAX).pow(3u32));
+
+    let n = product_of(&[2, 2, 3, 7, 7]);
+    assert_eq!(factorize(&n), factors(&[(2, 2), (3, 1), (7, 2)]));
+}
+
+#[test]
+fn test_pollard_rho() {
+    assert_eq!(pollard_rho(&BigInt::from(8051)), Some(BigInt::from(97)));
+    let n = BigInt::from(1_000_003u64 * 1_000_033);
+    let factor = pollard_rho(&n).expect("a factor");
+    assert!(factor == 1_000_003 || factor == 1_000_033);
+
+    for n in [0, 1, 2, 3, 97, 1_000_000_007] {
+        assert_eq!(pollard_rho(&BigInt::from(n)), None);
+    }
+    assert_eq!(pollard_rho(&((BigInt::from(1) << 127) - 1)), None);
+}
+
+#[test]
+fn test_pollard_rho_retries() {
+    // With c = 1 from x = 2 the cycle closes modulo n itself for these, and for the last three
+    // with c = 2 from x = 3 as well.
+    for n in [25u32, 169, 703, 1363, 1681] {
+        let factor = pollard_rho(&BigInt::from(n)).expect("a factor");
+        assert!(factor > 1 && factor < n && BigInt::from(n).is_divisible(&factor), "n = {}", n);
+    }
+
+    // Every odd composite below 10**5 gets split.
+    for n in (9..100_000u32).step_by(2) {
+        let n = BigInt::from(n);
+        if n.is_probably_prime(30) == IsPrime::No {
+            assert!(pollard_rho(&n).is_some(), "n = {}", n);
+        }
+    }
+}
+
+#[test]
+fn test_pollard_p_minus_one() {
+    // 1000033 - 1 = 2**5 * 3 * 11 * 947, but 1000003 - 1 = 2 * 3 * 166667.
+    let n = BigInt::from(1_000_003u64 * 1_000_033);
+    assert_eq!(pollard_p_minus_one(&n, 1000), Some(BigInt::from(1_000_033)));
+    assert_eq!(pollard_p_minus_one(&n, 946), None);
+    // Both p - 1 are smooth by the time 166667 is reached, but 1000033 shows first.
+    assert_eq!(pollard_p_minus_one(&n, 200_000), Some(BigInt::from(1_000_033)));
+    for bound in [0, 1] {
+        assert_eq!(pollard_p_minus_one(&n, bound), None);
+    }
+
+    assert_eq!(pollard_p_minus_one(&BigInt::from(1_000_000), 10), Some(BigInt::from(2)));
+    for n in [0, 1, 2, 3, 97, 1_000_000_007] {
+        assert_eq!(pollard_p_minus_one(&BigInt::from(n), 1000), None);
+    }
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn test_factorize_racing() {
+    let semiprime = BigInt::from(1_000_003u64 * 1_000_033);
+    assert_eq!(
+        factorize_racing(&semiprime),
+        factors(&[(1_000_003, 1), (1_000_033, 1)])
+    );
+
+    for n in [1u64, 2, 360, 1_000_003 * 1_000_003, 600_851_475_143, 1 << 40] {
+        let n = BigInt::from(n);
+        assert_eq!(factorize_racing(&n), factorize(&n), "n = {}", n);
+    }
+    // Both p - 1 have a large prime factor, so p - 1 fails and rho or CFRAC has to win.
+    let n = BigInt::from(4_294_967_291u64) * 4_294_967_279u64;
+    assert_eq!(
+        factorize_racing(&n),
+        factors(&[(4_294_967_279, 1), (4_294_967_291, 1)])
+    );
+}
+
+#[test]
+fn test_factor_cache() {
+    let mut cache = FactorCache::with_capacity(NonZeroUsize::new(2).unwrap());
+    let n = BigInt::from(1_000_003u64 * 1_000_033);
+
+    let first = cache.factorize(&n);
+    assert_eq!((cache.hits(), cache.misses()), (0, 1));
+    assert_eq!(cache.factorize(&n), first);
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+    // 360 and then 1001 push n out, as it is the least recently used.
+    cache.factorize(&BigInt::from(360));
+    cache.factorize(&n);
+    cache.factorize(&BigInt::from(1001));
+    assert_eq!((cache.hits(), cache.misses()), (2, 3));
+    assert_eq!(cache.len(), 2);
+    cache.factorize(&n);
+    assert_eq!((cache.hits(), cache.misses()), (3, 3));
+    cache.factorize(&BigInt::from(360));
+    assert_eq!((cache.hits(), cache.misses()), (3, 4));
+}
+
+#[test]
+fn test_cfrac_factor() {
+    // Two primes near 10**9, a 60 bit semiprime.
+    let p = BigInt::from(1_000_000_007);
+    let q = BigInt::from(1_000_000_009);
+    let n = BigInt::from(&p * &q);
+    let factor = cfrac_factor(&n, 200_000).expect("a factor");
+    assert!(factor == p || factor == q);
+
+    for (p, q) in [(1000003u64, 1000033u64), (65537, 4294967291), (101, 103)] {
+        let n = BigInt::from(p) * q;
+        let factor = cfrac_factor(&n, 100_000).expect("a factor");
+        assert!(factor == p || factor == q, "{} = {} * {}", n, p, q);
+    }
+
+    // Square, even and more than two factors.
+    assert_eq!(cfrac_factor(&BigInt::from(10007 * 10007), 100), Some(10007.into()));
+    assert_eq!(cfrac_factor(&BigInt::from(2 * 10007), 100), Some(2.into()));
+    let n = BigInt::from(3u64 * 1009 * 1000003);
+    let factor = cfrac_factor(&n, 100_000).expect("a factor");
+    assert!(factor != 1 && factor != n && n.is_divisible(&factor));
+}
+
+#[test]
+fn test_cfrac_factor_gives_up() {
+    for n in [0, 1, 2, 3, 97, 1_000_000_007] {
+        assert_eq!(cfrac_factor(&BigInt::from(n), 1000), None);
+    }
+
+    // Without any terms there are no relations to combine.
+    let n = BigInt::from(1_000_000_007u64 * 1_000_000_009);
+    assert_eq!(cfrac_factor(&n, 0), None);
+}
+
+#[test]
+fn test_quadratic_sieve() {
+    // Two primes near 2**25, a 51 bit semiprime.
+    let (p, q) = (BigInt::from(33554393), BigInt::from(33555439));
+    let n = BigInt::from(&p * &q);
+    let factor = quadratic_sieve(&n).expect("a factor");
+    assert!(factor == p || factor == q);
+    assert!(n.is_divisible(&factor));
+
+    for (p, q) in [(1000003u64, 1000033u64), (65537, 4294967291), (274877906899, 549755813911)] {
+        let n = BigInt::from(p) * q;
+        let factor = quadratic_sieve(&n).expect("a factor");
+        assert!(factor == p || factor == q, "{} = {} * {}", n, p, q);
+    }
+
+    // Square, even and a small factor in the factor base.
+    assert_eq!(quadratic_sieve(&BigInt::from(10007 * 10007)), Some(10007.into()));
+    assert_eq!(quadratic_sieve(&BigInt::from(2 * 10007)), Some(2.into()));
+    assert_eq!(quadratic_sieve(&BigInt::from(3 * 1_000_003)), Some(3.into()));
+
+    for n in [0, 1, 2, 3, 97, 1_000_000_007] {
+        assert_eq!(quadratic_sieve(&BigInt::from(n)), None);
+    }
+}
+
+#[test]
+fn test_sqrt_mod_prime() {
+    for p in crate::esieve::sieve(2000).unwrap() {
+        let p = p as u64;
+        for x in 0..p {
+            let root = sqrt_mod_prime(x * x % p, p);
+            assert_eq!(root * root % p, x * x % p, "x = {}, p = {}", x, p);
+        }
+    }
+}
+
+#[test]
+fn test_find_dependencies() {
+    let rows: Vec<BitVector> = [0b011u64, 0b110, 0b101, 0b111]
+        .iter()
+        .map(|bits| {
+            let mut row = BitVector::zeros(3);
+            for column in 0..3 {
+                if bits & (1 << column) != 0 {
+                    row.flip(column);
+                }
+            }
+            row
+        })
+        .collect();
+
+    let dependencies = gf2::find_dependencies(&rows, 3);
+    assert!(!dependencies.is_empty());
+    for dependency in dependencies {
+        let mut sum = BitVector::zeros(3);
+        for i in dependency {
+            for column in 0..3 {
+                if rows[i].get(column) {
+                    sum.flip(column);
+                }
+            }
+        }
+        assert_eq!(sum, BitVector::zeros(3));
+    }
+}
@@ -0,0 +1,69 @@
+//! Linear algebra over GF(2), for finding the congruences of squares that CFRAC and the quadratic
+//! sieve are built on.
+
+/// A vector over GF(2), packed 64 entries to a word.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct BitVector {
+    words: Vec<u64>,
+}
+
+impl BitVector {
+    pub(super) fn zeros(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    pub(super) fn get(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub(super) fn flip(&mut self, i: usize) {
+        self.words[i / 64] ^= 1 << (i % 64);
+    }
+
+    fn add_assign(&mut self, other: &BitVector) {
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word ^= other;
+        }
+    }
+}
+
+/// Finds subsets of `rows` that sum to zero, given as lists of row indices.
+///
+/// This is plain Gaussian elimination, tracking which of the original rows have been added into
+/// each row. Every row that ends up without a pivot has been zeroed out, and its history is a
+/// dependency.
+pub(super) fn find_dependencies(rows: &[BitVector], columns: usize) -> Vec<Vec<usize>> {
+    let mut matrix = rows.to_vec();
+    let mut history: Vec<BitVector> = (0..rows.len())
+        .map(|i| {
+            let mut v = BitVector::zeros(rows.len());
+            v.flip(i);
+            v
+        })
+        .collect();
+    let mut is_pivot = vec![false; rows.len()];
+
+    for column in 0..columns {
+        let Some(pivot) = (0..matrix.len()).find(|r| !is_pivot[*r] && matrix[*r].get(column))
+        else {
+            continue;
+        };
+        is_pivot[pivot] = true;
+
+        let pivot_row = matrix[pivot].clone();
+        let pivot_history = history[pivot].clone();
+        for r in 0..matrix.len() {
+            if r != pivot && matrix[r].get(column) {
+                matrix[r].add_assign(&pivot_row);
+                history[r].add_assign(&pivot_history);
+            }
+        }
+    }
+
+    (0..rows.len())
+        .filter(|r| !is_pivot[*r])
+        .map(|r| (0..rows.len()).filter(|i| history[r].get(*i)).collect())
+        .collect()
+}
@@ -1,9 +1,28 @@
-use rug::{Assign, Integer};
+use rug::Integer;
 
+pub mod esieve;
+pub mod factorization;
+pub mod number_theory;
 pub mod primality;
 
 pub type BigInt = Integer;
 
+/// Writes `n` in base `radix`, using the letters `a` to `z` for the digits above 9.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::format_in_base;
+/// assert_eq!(format_in_base(&255.into(), 16), "ff");
+/// assert_eq!(format_in_base(&(-8).into(), 2), "-1000");
+/// ```
+///
+/// # Panics
+/// - `radix < 2` or `radix > 36`
+pub fn format_in_base(n: &BigInt, radix: i32) -> String {
+    assert!((2..=36).contains(&radix), "the radix must be in [2, 36]");
+    n.to_string_radix(radix)
+}
 
 #[cfg(test)]
 mod tests {
@@ -22,4 +41,22 @@ mod tests {
 
         assert_eq!(int, 1);
     }
+
+    #[test]
+    fn test_format_in_base() {
+        let n = Integer::from(255);
+        assert_eq!(super::format_in_base(&n, 16), "ff");
+        assert_eq!(super::format_in_base(&n, 2), "11111111");
+        assert_eq!(super::format_in_base(&n, 8), "377");
+        assert_eq!(super::format_in_base(&n, 10), "255");
+        assert_eq!(super::format_in_base(&n, 36), "73");
+        assert_eq!(super::format_in_base(&Integer::from(0), 7), "0");
+        assert_eq!(super::format_in_base(&Integer::from(-255), 16), "-ff");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_format_in_base_bad_radix() {
+        super::format_in_base(&Integer::from(255), 37);
+    }
 }
@@ -0,0 +1,834 @@
+//! # Number theory
+//! Assorted number theoretic functions that build on the primality tests and the sieve. They favour
+//! clarity over speed, like the rest of the crate.
+
+#[cfg(test)]
+mod number_theory_tests;
+
+use std::{
+    num::NonZeroU32,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rug::{integer::IsPrime, ops::Pow, rand::RandState};
+
+use super::*;
+use crate::{
+    factorization::factorize,
+    primality::{miller_rabin_rounds, MillerRabinOracle, Primality, PrimalityOracle},
+};
+
+/// Finds a Goldbach partition of `n`: two primes `p <= q` with `p + q = n`.
+///
+/// The partition with the smallest `p` is returned, as `(p, q)`. Goldbach's conjecture says every
+/// even `n > 2` has one, and it has been checked far beyond anything this will be used for.
+///
+/// Returns `None` if `n` is odd or `n <= 2`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(goldbach_partition(&28.into()), Some((5.into(), 23.into())));
+/// assert_eq!(goldbach_partition(&27.into()), None);
+/// ```
+pub fn goldbach_partition(n: &BigInt) -> Option<(BigInt, BigInt)> {
+    if *n <= 2 || n.is_odd() {
+        return None;
+    }
+
+    let half = BigInt::from(n >> 1);
+    let mut p = BigInt::from(2);
+    while p <= half {
+        let q = BigInt::from(n - &p);
+        if q.is_probably_prime(30) != IsPrime::No {
+            return Some((p, q));
+        }
+        p.next_prime_mut();
+    }
+
+    None
+}
+
+/// Draws a random prime with exactly `bits` bits, i.e. in `[2**(bits - 1), 2**bits)`.
+///
+/// # Panics
+/// - `bits < 2`
+pub fn random_prime(bits: u32, rand: &mut RandState) -> BigInt {
+    assert!(bits >= 2, "there are no primes with fewer than 2 bits");
+
+    loop {
+        let mut candidate = BigInt::from(BigInt::random_bits(bits - 1, rand));
+        candidate.set_bit(bits - 1, true);
+        candidate.next_prime_mut();
+        // next_prime can step past 2**bits, in which case we draw again.
+        if candidate.significant_bits() == bits {
+            return candidate;
+        }
+    }
+}
+
+/// Generates an RSA-style modulus `n = p q` from two distinct random primes of about `bits / 2`
+/// bits each, returned as `(n, p, q)`.
+///
+/// With a `seed` the output is reproducible, without one the generator is seeded from the clock.
+/// Either way this is for experiments and teaching only, the generator is not cryptographically
+/// secure.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// let (n, p, q) = generate_rsa_modulus(64, Some(1));
+/// assert_eq!(n, p * q);
+/// ```
+///
+/// # Panics
+/// - `bits < 4`
+pub fn generate_rsa_modulus(bits: u32, seed: Option<u64>) -> (BigInt, BigInt, BigInt) {
+    assert!(bits >= 4, "need at least 2 bits for each prime");
+
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+    });
+    let mut rand = RandState::new();
+    rand.seed(&BigInt::from(seed));
+
+    let p = random_prime(bits / 2, &mut rand);
+    let q = loop {
+        let q = random_prime(bits - bits / 2, &mut rand);
+        if q != p {
+            break q;
+        }
+    };
+
+    (BigInt::from(&p * &q), p, q)
+}
+
+/// Builds `count` random odd integers of exactly `bits` bits, the same ones for the same `seed`.
+///
+/// Meant as reproducible input for the benchmarks, so it is only built with the `bench` feature.
+/// Odd numbers keep the tests from bailing out on the first even check.
+///
+/// # Panics
+/// - `bits < 2`
+#[cfg(feature = "bench")]
+pub fn bench_inputs(count: usize, bits: u32, seed: u64) -> Vec<BigInt> {
+    assert!(bits >= 2, "an odd number with its top bit set needs at least 2 bits");
+
+    let mut rand = RandState::new();
+    rand.seed(&BigInt::from(seed));
+
+    (0..count)
+        .map(|_| {
+            let mut n = BigInt::from(BigInt::random_bits(bits, &mut rand));
+            n.set_bit(bits - 1, true);
+            n.set_bit(0, true);
+            n
+        })
+        .collect()
+}
+
+/// The Jacobi symbol `(a|n)` for odd positive `n`, generalizing the Legendre symbol.
+///
+/// For a prime `n` it is 1 if `a` is a nonzero square mod `n`, -1 if it is not a square and 0 if
+/// `n | a`. For composite `n` it is the product of the Legendre symbols of the prime factors, so
+/// 1 no longer guarantees a square.
+///
+/// Computed with quadratic reciprocity, much like the Euclidean algorithm, without factoring `n`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(jacobi_symbol(&2.into(), &7.into()), 1);
+/// assert_eq!(jacobi_symbol(&3.into(), &7.into()), -1);
+/// assert_eq!(jacobi_symbol(&14.into(), &7.into()), 0);
+/// ```
+///
+/// # Panics
+/// - `n` is even or not positive.
+pub fn jacobi_symbol(a: &BigInt, n: &BigInt) -> i32 {
+    assert!(n.is_positive() && n.is_odd(), "n must be odd and positive");
+
+    let mut a = a.clone().modulo(n);
+    let mut n = n.clone();
+    let mut result = 1;
+
+    while a != 0 {
+        // (2|n) = -1 exactly when n = 3 or 5 mod 8.
+        let twos = a.find_one(0).expect("a is nonzero");
+        a >>= twos;
+        if twos % 2 == 1 && matches!(n.mod_u(8), 3 | 5) {
+            result = -result;
+        }
+
+        // Quadratic reciprocity, (a|n) = -(n|a) only when both are 3 mod 4.
+        std::mem::swap(&mut a, &mut n);
+        if a.mod_u(4) == 3 && n.mod_u(4) == 3 {
+            result = -result;
+        }
+        a = a.modulo(&n);
+    }
+
+    if n == 1 {
+        result
+    } else {
+        0
+    }
+}
+
+/// The Kronecker symbol `(a|n)`, extending [jacobi_symbol] to every integer `n`.
+///
+/// Even and negative `n` are handled by factoring out 2 and -1, with
+/// ```text
+///     (a|2)  = 0 if a is even, 1 if a = +-1 mod 8 and -1 if a = +-3 mod 8
+///     (a|-1) = -1 if a < 0, otherwise 1
+///     (a|0)  = 1 if a = +-1, otherwise 0
+/// ```
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(kronecker_symbol(&3.into(), &8.into()), -1);
+/// assert_eq!(kronecker_symbol(&(-5).into(), &(-3).into()), -1);
+/// assert_eq!(kronecker_symbol(&2.into(), &0.into()), 0);
+/// ```
+pub fn kronecker_symbol(a: &BigInt, n: &BigInt) -> i32 {
+    if *n == 0 {
+        return if *a.as_abs() == 1 { 1 } else { 0 };
+    }
+
+    let mut result = 1;
+    if n.is_negative() && a.is_negative() {
+        result = -result;
+    }
+
+    let mut n = BigInt::from(n.abs_ref());
+    let twos = n.find_one(0).expect("n is nonzero");
+    if twos > 0 {
+        if a.is_even() {
+            return 0;
+        }
+        n >>= twos;
+        if twos % 2 == 1 && matches!(a.mod_u(8), 3 | 5) {
+            result = -result;
+        }
+    }
+
+    result * jacobi_symbol(a, &n)
+}
+
+/// Whether `gcd(a, b) = 1`, so that `a` and `b` share no prime factor.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert!(are_coprime(&14.into(), &15.into()));
+/// assert!(!are_coprime(&12.into(), &18.into()));
+/// ```
+pub fn are_coprime(a: &BigInt, b: &BigInt) -> bool {
+    BigInt::from(a.gcd_ref(b)) == 1
+}
+
+/// Counts the integers `k` in `[1, bound)` coprime to `n`.
+///
+/// Uses inclusion-exclusion over the distinct primes `p | n`: from the `bound - 1` candidates it
+/// takes away the multiples of each `p`, adds back the multiples of each `p q`, and so on. That is
+/// `2**w` terms for `w` distinct primes, after a [factorize]. With `bound = n` the count is
+/// `phi(n)` for every `n > 1`, see [euler_totient].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// // 1, 5, 7, 11, 13, 17, 19
+/// assert_eq!(count_coprimes_below(&12.into(), &20.into()), 7);
+/// assert_eq!(count_coprimes_below(&12.into(), &12.into()), euler_totient(&12.into()));
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+pub fn count_coprimes_below(n: &BigInt, bound: &BigInt) -> BigInt {
+    let last = BigInt::from(bound - 1);
+    if last < 1 {
+        return BigInt::new();
+    }
+
+    // Every square-free divisor d of n, with the sign (-1)**(number of primes in d).
+    let mut divisors = vec![(BigInt::from(1), true)];
+    for (p, _) in factorize(n) {
+        let with_p: Vec<(BigInt, bool)> = divisors
+            .iter()
+            .map(|(d, positive)| (BigInt::from(d * &p), !positive))
+            .collect();
+        divisors.extend(with_p);
+    }
+
+    divisors
+        .iter()
+        .fold(BigInt::new(), |count, (d, positive)| {
+            let multiples = BigInt::from(&last / d);
+            if *positive {
+                count + multiples
+            } else {
+                count - multiples
+            }
+        })
+}
+
+/// Euler's totient `phi(n)`, the number of integers in `[1, n]` coprime to `n`.
+///
+/// Computed from the factorization as `phi(n) = n (1 - 1/p1) (1 - 1/p2) ...`, so it is only as
+/// fast as [factorize].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(euler_totient(&12.into()), 4);
+/// assert_eq!(euler_totient(&97.into()), 96);
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+pub fn euler_totient(n: &BigInt) -> BigInt {
+    let mut totient = n.clone();
+    for (p, _) in factorize(n) {
+        totient /= &p;
+        totient *= BigInt::from(&p - 1);
+    }
+    totient
+}
+
+/// The Carmichael function `lambda(n)`, the smallest `m > 0` with `a**m = 1 mod n` for every `a`
+/// coprime to `n`. It is the exponent of the multiplicative group mod `n` and divides
+/// [euler_totient].
+///
+/// Computed as the lcm of `lambda(p**k)` over the prime powers of `n`, where `lambda(p**k)` is
+/// `phi(p**k)`, except that `lambda(2**k) = 2**(k - 2)` for `k >= 3`. Like [euler_totient] this is
+/// only as fast as [factorize].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(carmichael_lambda(&8.into()), 2);
+/// assert_eq!(carmichael_lambda(&15.into()), 4);
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+pub fn carmichael_lambda(n: &BigInt) -> BigInt {
+    factorize(n)
+        .into_iter()
+        .fold(BigInt::from(1), |lambda, (p, k)| {
+            let prime_power_lambda = if p == 2 && k >= 3 {
+                BigInt::from(1) << (k - 2)
+            } else {
+                BigInt::from((&p).pow(k - 1)) * (p - 1)
+            };
+            lambda.lcm(&prime_power_lambda)
+        })
+}
+
+/// The radical of `n`, the product of its distinct prime factors. For example `rad(12) = 2 * 3`.
+///
+/// Used in the abc conjecture, which compares `c` against `rad(a b c)`. Like [euler_totient] this
+/// is only as fast as [factorize].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(radical(&12.into()), 6);
+/// assert_eq!(radical(&1.into()), 1);
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+pub fn radical(n: &BigInt) -> BigInt {
+    factorize(n)
+        .into_iter()
+        .fold(BigInt::from(1), |radical, (p, _)| radical * p)
+}
+
+/// The Möbius function `mu(n)`: 0 if `n` has a square factor, otherwise `(-1)**k` where `k` is the
+/// number of prime factors. Like [euler_totient] this is only as fast as [factorize].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(mobius(&30.into()), -1);
+/// assert_eq!(mobius(&12.into()), 0);
+/// assert_eq!(mobius(&1.into()), 1);
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+pub fn mobius(n: &BigInt) -> i32 {
+    let factors = factorize(n);
+    if factors.iter().any(|(_, e)| *e > 1) {
+        0
+    } else if factors.len().is_multiple_of(2) {
+        1
+    } else {
+        -1
+    }
+}
+
+/// The multiplicative order of `a` modulo `n`: the smallest `k > 0` with `a**k = 1 mod n`.
+///
+/// By Lagrange's theorem the order divides `phi(n)`. Starting from `phi(n)`, each prime factor is
+/// divided out for as long as the power stays 1.
+///
+/// Returns `None` when `gcd(a, n) != 1`, since then no power of `a` is 1.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(multiplicative_order(&2.into(), &7.into()), Some(3.into()));
+/// assert_eq!(multiplicative_order(&2.into(), &6.into()), None);
+/// ```
+///
+/// # Panics
+/// - `n < 1`
+pub fn multiplicative_order(a: &BigInt, n: &BigInt) -> Option<BigInt> {
+    assert!(n.is_positive());
+
+    if !are_coprime(a, n) {
+        return None;
+    }
+
+    let totient = euler_totient(n);
+    let mut order = totient.clone();
+    for (q, _) in factorize(&totient) {
+        while order.is_divisible(&q) {
+            let candidate = BigInt::from(&order / &q);
+            let power = BigInt::from(
+                a.pow_mod_ref(&candidate, n)
+                    .expect("exponent is non-negative"),
+            );
+            if power != BigInt::from(1).modulo(n) {
+                break;
+            }
+            order = candidate;
+        }
+    }
+    Some(order)
+}
+
+/// The length of the repeating part of the decimal expansion of `1 / p`, for a prime `p`.
+///
+/// `1 / p` repeats with period `k` when `p | 10**k - 1`, so the period is the
+/// [multiplicative_order] of 10 modulo `p`. Returns `None` if `p` isn't prime or is 2 or 5, whose
+/// reciprocals terminate.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// // 1/7 = 0.142857142857...
+/// assert_eq!(decimal_period(&7.into()), Some(6.into()));
+/// assert_eq!(decimal_period(&5.into()), None);
+/// ```
+pub fn decimal_period(p: &BigInt) -> Option<BigInt> {
+    if *p <= 2 || *p == 5 || p.is_probably_prime(30) == IsPrime::No {
+        return None;
+    }
+    multiplicative_order(&BigInt::from(10), p)
+}
+
+/// The Fermat quotient `q_p(a) = (a**(p - 1) - 1) / p mod p`, for a prime `p` not dividing `a`.
+///
+/// By Fermat's little theorem `p | a**(p - 1) - 1`, so the quotient is an integer. It is 0 exactly
+/// when `p**2 | a**(p - 1) - 1`, which for `a = 2` makes `p` a Wieferich prime. Only 1093 and 3511
+/// are known. Returns `None` if `p` isn't prime or divides `a`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// // (2**6 - 1) / 7 = 9 = 2 mod 7
+/// assert_eq!(fermat_quotient(&2.into(), &7.into()), Some(2.into()));
+/// assert_eq!(fermat_quotient(&2.into(), &1093.into()), Some(0.into()));
+/// ```
+pub fn fermat_quotient(a: &BigInt, p: &BigInt) -> Option<BigInt> {
+    if *p < 2 || p.is_probably_prime(30) == IsPrime::No || a.is_divisible(p) {
+        return None;
+    }
+
+    // Working mod p**2 keeps the part of a**(p - 1) - 1 that survives the division by p.
+    let p_squared = BigInt::from(p.square_ref());
+    let power = BigInt::from(
+        a.pow_mod_ref(&BigInt::from(p - 1), &p_squared)
+            .expect("exponent is non-negative"),
+    );
+    Some((power - 1) / p)
+}
+
+/// Whether `p` is a Wieferich prime, a prime with `2**(p - 1) = 1 mod p**2`. That is, whether its
+/// [fermat_quotient] to base 2 is 0.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert!(is_wieferich(&1093.into()));
+/// assert!(!is_wieferich(&1091.into()));
+/// ```
+pub fn is_wieferich(p: &BigInt) -> bool {
+    fermat_quotient(&BigInt::from(2), p).is_some_and(|quotient| quotient == 0)
+}
+
+/// The Wieferich primes `p <= up_to`, found by testing each prime from [esieve::sieve] with
+/// [is_wieferich].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(search_wieferich(5000), [1093, 3511]);
+/// ```
+///
+/// # Panics
+/// - The sieve up to `up_to` can't be allocated.
+pub fn search_wieferich(up_to: usize) -> Vec<usize> {
+    // Below 2 there are no primes to search.
+    if up_to < 2 {
+        return vec![];
+    }
+
+    esieve::sieve(up_to)
+        .unwrap_or_else(|e| panic!("can't sieve the primes up to {}: {}", up_to, e))
+        .into_iter()
+        .filter(|p| is_wieferich(&BigInt::from(*p)))
+        .collect()
+}
+
+/// Finds the smallest primitive root modulo a prime `p`, i.e. a generator of the multiplicative
+/// group mod `p`.
+///
+/// `g` generates the group when its order is `p - 1`, which holds exactly when
+/// `g**((p - 1) / q) != 1 mod p` for every prime `q | p - 1`. Candidates are tried from 2 upwards,
+/// and primitive roots are common enough that few are needed.
+///
+/// Returns `None` if `p` isn't prime. Composite moduli can have primitive roots too, but those
+/// aren't searched for.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(primitive_root(&7.into()), Some(3.into()));
+/// assert_eq!(primitive_root(&8.into()), None);
+/// ```
+pub fn primitive_root(p: &BigInt) -> Option<BigInt> {
+    if *p < 2 || p.is_probably_prime(30) == IsPrime::No {
+        return None;
+    }
+    if *p == 2 {
+        return Some(BigInt::from(1));
+    }
+
+    let p_minus_one = BigInt::from(p - 1);
+    let exponents: Vec<BigInt> = factorize(&p_minus_one)
+        .into_iter()
+        .map(|(q, _)| BigInt::from(&p_minus_one / &q))
+        .collect();
+
+    let mut g = BigInt::from(2);
+    loop {
+        let generates = exponents.iter().all(|exponent| {
+            g.pow_mod_ref(exponent, p)
+                .map(BigInt::from)
+                .expect("exponent is non-negative")
+                != 1
+        });
+        if generates {
+            return Some(g);
+        }
+        g += 1;
+    }
+}
+
+/// Lists the probable primes in `[lo, hi]`, testing each odd candidate with 25 rounds of
+/// [miller_rabin_rounds].
+///
+/// Unlike the sieve this works for ranges far beyond `usize`, such as around `10**100`, but it
+/// costs a full primality test per candidate. For ranges that start low, the sieve is much faster.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// let primes = primes_in_range(&10.into(), &30.into());
+/// assert_eq!(primes, [11, 13, 17, 19, 23, 29]);
+/// ```
+pub fn primes_in_range(lo: &BigInt, hi: &BigInt) -> Vec<BigInt> {
+    let rounds = NonZeroU32::new(25).unwrap();
+    let mut primes = vec![];
+
+    if *lo <= 2 && *hi >= 2 {
+        primes.push(BigInt::from(2));
+    }
+
+    // The first odd candidate that is at least 3.
+    let mut candidate = lo.clone().max(BigInt::from(3));
+    if candidate.is_even() {
+        candidate += 1;
+    }
+    while candidate <= *hi {
+        if miller_rabin_rounds(&candidate, rounds) {
+            primes.push(candidate.clone());
+        }
+        candidate += 2;
+    }
+    primes
+}
+
+/// The smallest prime `p > n`, by 25 rounds of [miller_rabin_rounds] on each candidate.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(next_prime(&13.into()), 17);
+/// assert_eq!(next_prime(&(-5).into()), 2);
+/// ```
+pub fn next_prime(n: &BigInt) -> BigInt {
+    next_prime_with_oracle(n, &MillerRabinOracle)
+}
+
+/// The same as [next_prime], but asks `oracle` which candidates are prime. Even candidates above 2
+/// are skipped without asking.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{number_theory::*, primality::*};
+/// assert_eq!(next_prime_with_oracle(&13.into(), &MillerRabinOracle), 17);
+/// ```
+pub fn next_prime_with_oracle(n: &BigInt, oracle: &impl PrimalityOracle) -> BigInt {
+    if *n < 2 {
+        return BigInt::from(2);
+    }
+
+    // The first odd candidate above n.
+    let mut candidate = BigInt::from(n + 1);
+    if candidate.is_even() {
+        candidate += 1;
+    }
+    while oracle.is_prime(&candidate) == Primality::Composite {
+        candidate += 2;
+    }
+    candidate
+}
+
+/// Whether the Cullen number `n 2**n + 1` is prime, by 25 rounds of [miller_rabin_rounds].
+///
+/// Cullen primes are rare: the only `n < 4000` giving one are 1 and 141.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert!(is_cullen_prime(1));
+/// assert!(!is_cullen_prime(2));
+/// ```
+pub fn is_cullen_prime(n: u32) -> bool {
+    let cullen = (BigInt::from(n) << n) + 1u32;
+    miller_rabin_rounds(&cullen, NonZeroU32::new(25).unwrap())
+}
+
+/// Whether the Woodall number `n 2**n - 1` is prime, by 25 rounds of [miller_rabin_rounds].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// // 3 * 2**3 - 1 = 23
+/// assert!(is_woodall_prime(3));
+/// assert!(!is_woodall_prime(4));
+/// ```
+pub fn is_woodall_prime(n: u32) -> bool {
+    let woodall = (BigInt::from(n) << n) - 1u32;
+    miller_rabin_rounds(&woodall, NonZeroU32::new(25).unwrap())
+}
+
+/// Computes `n! mod p` by multiplying `1, 2, ..., n` modulo `p`.
+///
+/// When `n >= p`, `p` is itself one of the factors, so the result is 0 without any work.
+/// Otherwise it takes `n` multiplications, so `n` should be small enough to count to. `p` doesn't
+/// have to be prime, though with Wilson's theorem, `(p - 1)! = -1 mod p` exactly when `p` is.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(factorial_mod(&5.into(), &7.into()), 1);
+/// assert_eq!(factorial_mod(&10.into(), &7.into()), 0);
+/// ```
+///
+/// # Panics
+/// - `n < 0`
+/// - `p < 1`
+pub fn factorial_mod(n: &BigInt, p: &BigInt) -> BigInt {
+    assert!(!n.is_negative(), "n must not be negative");
+    assert!(p.is_positive(), "p must be positive");
+
+    if n >= p {
+        return BigInt::from(0);
+    }
+
+    let mut factorial = BigInt::from(1).modulo(p);
+    let mut i = BigInt::from(2);
+    while i <= *n {
+        factorial *= &i;
+        factorial.modulo_mut(p);
+        i += 1;
+    }
+    factorial
+}
+
+/// Computes the binomial coefficient `C(n, k) mod p` for a prime `p` with Lucas' theorem.
+///
+/// Writing `n` and `k` in base `p`, `C(n, k)` is congruent to the product of `C(n_i, k_i)` over
+/// their digits, and each of those is small enough to compute directly. The work grows with the
+/// number of digits rather than with `n`, so `n` can be huge as long as `p` is small enough to
+/// count to. `C(n, k)` is 0 for `k < 0` or `k > n`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// // C(10, 3) = 120
+/// assert_eq!(binomial_mod_prime(&10.into(), &3.into(), &7.into()), 1);
+/// ```
+///
+/// # Panics
+/// - `n < 0`
+/// - `p < 2`, or `p` isn't prime and a digit's binomial can't be divided out
+pub fn binomial_mod_prime(n: &BigInt, k: &BigInt, p: &BigInt) -> BigInt {
+    assert!(!n.is_negative(), "n must not be negative");
+    assert!(*p >= 2, "p must be a prime");
+
+    if k.is_negative() || k > n {
+        return BigInt::from(0);
+    }
+
+    let mut binomial = BigInt::from(1);
+    let mut n = n.clone();
+    let mut k = k.clone();
+    // Once k runs out of digits, the rest are C(n_i, 0) = 1.
+    while !k.is_zero() {
+        let n_digit = BigInt::from(n.modulo_ref(p));
+        let k_digit = BigInt::from(k.modulo_ref(p));
+        if k_digit > n_digit {
+            return BigInt::from(0);
+        }
+        binomial *= small_binomial_mod_prime(&n_digit, &k_digit, p);
+        binomial.modulo_mut(p);
+        n /= p;
+        k /= p;
+    }
+    binomial.modulo(p)
+}
+
+/// `C(n, k) mod p` for `0 <= k <= n < p`, as `n (n - 1) ... (n - k + 1) / k!`.
+fn small_binomial_mod_prime(n: &BigInt, k: &BigInt, p: &BigInt) -> BigInt {
+    let k = k.min(&BigInt::from(n - k)).clone();
+    let mut numerator = BigInt::from(1);
+    let mut denominator = BigInt::from(1);
+    let mut i = BigInt::from(0);
+    while i < k {
+        numerator *= BigInt::from(n - &i);
+        numerator.modulo_mut(p);
+        i += 1;
+        denominator *= &i;
+        denominator.modulo_mut(p);
+    }
+    let inverse = denominator
+        .invert(p)
+        .expect("k! is coprime to the prime p > k");
+    (numerator * inverse).modulo(p)
+}
+
+/// The quadratic residues modulo a prime `p`, the non-zero `x` with `x = i**2 mod p` for some `i`,
+/// in increasing order.
+///
+/// Since `i**2 = (p - i)**2`, squaring `i` in `[1, p / 2]` finds them all. For an odd prime there
+/// are `(p - 1) / 2` of them. The whole list is built, so this is only practical for small `p`,
+/// say below `10**7`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// assert_eq!(quadratic_residues(&7.into()), [1, 2, 4]);
+/// ```
+///
+/// # Panics
+/// - `p < 2`
+pub fn quadratic_residues(p: &BigInt) -> Vec<BigInt> {
+    assert!(*p >= 2, "p must be a prime");
+
+    let half = BigInt::from(p >> 1);
+    let mut residues = vec![];
+    let mut i = BigInt::from(1);
+    while i <= half {
+        residues.push(BigInt::from(i.square_ref()).modulo(p));
+        i += 1;
+    }
+    residues.sort();
+    residues.dedup();
+    residues
+}
+
+/// Computes `base**exp mod modulus` by left-to-right square-and-multiply, returning the result and
+/// every intermediate value, to show how the method works.
+///
+/// Starting from 1, each bit of `exp` from the top squares the running value, and a set bit then
+/// multiplies it by `base`. Both steps are recorded, so the trace has one entry per bit of `exp`
+/// plus one per set bit, and its last entry is the result. For `exp = 0` the trace is empty.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::number_theory::*;
+/// // 13 = 0b1101: square, multiply, square, multiply, square, square, multiply.
+/// let (result, trace) = mod_pow_trace(&3.into(), &13.into(), &7.into());
+/// assert_eq!(result, 3);
+/// assert_eq!(trace, [1, 3, 2, 6, 1, 1, 3]);
+/// ```
+///
+/// # Panics
+/// - `exp < 0`
+/// - `modulus < 1`
+pub fn mod_pow_trace(base: &BigInt, exp: &BigInt, modulus: &BigInt) -> (BigInt, Vec<BigInt>) {
+    assert!(!exp.is_negative(), "exp must not be negative");
+    assert!(modulus.is_positive(), "modulus must be positive");
+
+    let base = BigInt::from(base.modulo_ref(modulus));
+    let mut value = BigInt::from(1).modulo(modulus);
+    let mut trace = vec![];
+    for bit in (0..exp.significant_bits()).rev() {
+        value.square_mut();
+        value.modulo_mut(modulus);
+        trace.push(value.clone());
+
+        if exp.get_bit(bit) {
+            value *= &base;
+            value.modulo_mut(modulus);
+            trace.push(value.clone());
+        }
+    }
+    (value, trace)
+}
@@ -0,0 +1,547 @@
+use rug::ops::Pow;
+
+use super::*;
+
+#[test]
+fn test_goldbach_partition() {
+    assert_eq!(
+        goldbach_partition(&BigInt::from(28)),
+        Some((BigInt::from(5), BigInt::from(23)))
+    );
+    assert_eq!(
+        goldbach_partition(&BigInt::from(4)),
+        Some((BigInt::from(2), BigInt::from(2)))
+    );
+
+    for n in (4..1000).step_by(2) {
+        let (p, q) = goldbach_partition(&BigInt::from(n)).expect("a partition");
+        assert!(p <= q);
+        assert_eq!(BigInt::from(&p + &q), n);
+        assert_ne!(p.is_probably_prime(30), IsPrime::No);
+        assert_ne!(q.is_probably_prime(30), IsPrime::No);
+    }
+
+    let large = BigInt::from(BigInt::u_pow_u(10, 30));
+    let (p, q) = goldbach_partition(&large).expect("a partition");
+    assert_eq!(BigInt::from(&p + &q), large);
+
+    for n in [-4, 0, 1, 2, 3, 27] {
+        assert_eq!(goldbach_partition(&BigInt::from(n)), None);
+    }
+}
+
+#[test]
+fn test_random_prime() {
+    let mut rand = RandState::new();
+    for bits in [2, 3, 8, 17, 64, 200] {
+        for _ in 0..10 {
+            let p = random_prime(bits, &mut rand);
+            assert_eq!(p.significant_bits(), bits);
+            assert_ne!(p.is_probably_prime(30), IsPrime::No);
+        }
+    }
+}
+
+#[test]
+fn test_generate_rsa_modulus() {
+    for seed in 0..20 {
+        let (n, p, q) = generate_rsa_modulus(40, Some(seed));
+        assert_ne!(p, q);
+        assert_eq!(n, BigInt::from(&p * &q));
+        assert!((39..=40).contains(&n.significant_bits()));
+
+        // Small enough to factor by trial division.
+        let mut d = BigInt::from(2);
+        while !n.is_divisible(&d) {
+            d += 1;
+        }
+        let cofactor = BigInt::from(&n / &d);
+        assert!((d == p && cofactor == q) || (d == q && cofactor == p));
+    }
+
+    assert_eq!(
+        generate_rsa_modulus(512, Some(7)),
+        generate_rsa_modulus(512, Some(7))
+    );
+    let (n, _, _) = generate_rsa_modulus(512, None);
+    assert!((511..=512).contains(&n.significant_bits()));
+}
+
+#[test]
+#[cfg(feature = "bench")]
+fn test_bench_inputs() {
+    let inputs = bench_inputs(100, 128, 42);
+    assert_eq!(inputs.len(), 100);
+    assert!(inputs
+        .iter()
+        .all(|n| n.significant_bits() == 128 && n.is_odd()));
+    assert_eq!(inputs, bench_inputs(100, 128, 42));
+    assert_ne!(inputs, bench_inputs(100, 128, 43));
+}
+
+#[test]
+fn test_jacobi_symbol() {
+    // Quadratic residues mod 7 are 1, 2 and 4.
+    let expected = [0, 1, 1, -1, 1, -1, -1];
+    for (a, symbol) in expected.iter().enumerate() {
+        assert_eq!(jacobi_symbol(&BigInt::from(a), &BigInt::from(7)), *symbol);
+    }
+
+    for n in (1..200).step_by(2) {
+        for a in -50..250 {
+            let (a, n) = (BigInt::from(a), BigInt::from(n));
+            assert_eq!(jacobi_symbol(&a, &n), a.jacobi(&n), "({}|{})", a, n);
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_jacobi_symbol_even_n() {
+    jacobi_symbol(&BigInt::from(3), &BigInt::from(8));
+}
+
+#[test]
+fn test_kronecker_symbol() {
+    // Even n.
+    assert_eq!(kronecker_symbol(&BigInt::from(3), &BigInt::from(8)), -1);
+    assert_eq!(kronecker_symbol(&BigInt::from(7), &BigInt::from(2)), 1);
+    assert_eq!(kronecker_symbol(&BigInt::from(4), &BigInt::from(6)), 0);
+    // Negative n.
+    assert_eq!(kronecker_symbol(&BigInt::from(-1), &BigInt::from(-1)), -1);
+    assert_eq!(kronecker_symbol(&BigInt::from(2), &BigInt::from(-7)), 1);
+    // n = 0.
+    assert_eq!(kronecker_symbol(&BigInt::from(-1), &BigInt::from(0)), 1);
+    assert_eq!(kronecker_symbol(&BigInt::from(3), &BigInt::from(0)), 0);
+
+    for n in -100..100 {
+        for a in -100..100 {
+            let (a, n) = (BigInt::from(a), BigInt::from(n));
+            assert_eq!(kronecker_symbol(&a, &n), a.kronecker(&n), "({}|{})", a, n);
+            if n.is_positive() && n.is_odd() {
+                assert_eq!(kronecker_symbol(&a, &n), jacobi_symbol(&a, &n));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_are_coprime() {
+    assert!(are_coprime(&BigInt::from(14), &BigInt::from(15)));
+    assert!(!are_coprime(&BigInt::from(12), &BigInt::from(18)));
+    assert!(are_coprime(&BigInt::from(1), &BigInt::from(0)));
+    assert!(!are_coprime(&BigInt::from(0), &BigInt::from(0)));
+    assert!(!are_coprime(&BigInt::from(-6), &BigInt::from(9)));
+    let p = BigInt::from(1_000_000_007);
+    assert!(are_coprime(&p, &BigInt::from(1_000_000_009)));
+    assert!(!are_coprime(&p, &BigInt::from(&p * 3)));
+}
+
+#[test]
+fn test_count_coprimes_below() {
+    for n in 2..300u32 {
+        let n = BigInt::from(n);
+        assert_eq!(count_coprimes_below(&n, &n), euler_totient(&n), "n = {}", n);
+    }
+
+    // Straight from the definition.
+    for n in [1u32, 2, 12, 30, 97, 210, 1001] {
+        for bound in [0u32, 1, 2, 7, 100, 2500] {
+            let expected = (1..bound)
+                .filter(|k| are_coprime(&BigInt::from(*k), &BigInt::from(n)))
+                .count();
+            assert_eq!(
+                count_coprimes_below(&BigInt::from(n), &BigInt::from(bound)),
+                expected,
+                "n = {}, bound = {}",
+                n,
+                bound
+            );
+        }
+    }
+
+    assert_eq!(count_coprimes_below(&BigInt::from(1), &BigInt::from(1)), 0);
+    assert_eq!(count_coprimes_below(&BigInt::from(6), &BigInt::from(-5)), 0);
+}
+
+#[test]
+fn test_euler_totient() {
+    let expected = [1, 1, 2, 2, 4, 2, 6, 4, 6, 4, 10, 4];
+    for (n, totient) in (1..).zip(expected) {
+        assert_eq!(euler_totient(&BigInt::from(n)), totient);
+    }
+
+    for p in crate::primality::FIRST_100_PRIMES {
+        assert_eq!(euler_totient(&BigInt::from(*p)), p - 1);
+    }
+    assert_eq!(euler_totient(&BigInt::from(561)), 320);
+}
+
+#[test]
+fn test_carmichael_lambda() {
+    assert_eq!(carmichael_lambda(&BigInt::from(1)), 1);
+    assert_eq!(carmichael_lambda(&BigInt::from(2)), 1);
+    assert_eq!(carmichael_lambda(&BigInt::from(4)), 2);
+    assert_eq!(carmichael_lambda(&BigInt::from(8)), 2);
+    assert_eq!(carmichael_lambda(&BigInt::from(15)), 4);
+    assert_eq!(carmichael_lambda(&BigInt::from(1024)), 256);
+    // 561 = 3 * 11 * 17, lcm(2, 10, 16) = 80 divides 560 as Korselt's criterion wants.
+    assert_eq!(carmichael_lambda(&BigInt::from(561)), 80);
+
+    for p in crate::primality::FIRST_100_PRIMES {
+        assert_eq!(carmichael_lambda(&BigInt::from(*p)), p - 1);
+    }
+
+    // The largest multiplicative order of any unit.
+    for n in 2..200u32 {
+        let n = BigInt::from(n);
+        let largest_order = (1..n.to_u32().unwrap())
+            .filter_map(|a| multiplicative_order(&BigInt::from(a), &n))
+            .max()
+            .unwrap();
+        assert_eq!(carmichael_lambda(&n), largest_order, "n = {}", n);
+    }
+}
+
+#[test]
+fn test_mobius() {
+    let expected = [1, -1, -1, 0, -1, 1, -1, 0, 0, 1, -1, 0];
+    for (n, mu) in (1..).zip(expected) {
+        assert_eq!(mobius(&BigInt::from(n)), mu, "n = {}", n);
+    }
+
+    for p in crate::primality::FIRST_100_PRIMES {
+        assert_eq!(mobius(&BigInt::from(*p)), -1);
+        assert_eq!(mobius(&BigInt::from(p * p)), 0);
+    }
+    assert_eq!(mobius(&BigInt::from(2 * 3 * 5 * 7)), 1);
+    assert_eq!(mobius(&BigInt::from(1_000_003u64 * 1_000_033)), 1);
+}
+
+#[test]
+fn test_radical() {
+    assert_eq!(radical(&BigInt::from(1)), 1);
+    assert_eq!(radical(&BigInt::from(12)), 6);
+    assert_eq!(radical(&BigInt::from(1024)), 2);
+    assert_eq!(radical(&BigInt::from(2 * 2 * 3 * 5 * 5 * 5 * 7)), 210);
+
+    for p in crate::primality::FIRST_100_PRIMES {
+        assert_eq!(radical(&BigInt::from(*p)), *p);
+    }
+    let p = BigInt::from(1_000_000_007u64);
+    assert_eq!(radical(&p), p);
+    assert_eq!(radical(&BigInt::from((&p).pow(3))), p);
+}
+
+/// The order found by checking every power in turn.
+fn order_by_brute_force(a: u64, n: u64) -> u64 {
+    let mut power = a % n;
+    let mut k = 1;
+    while power != 1 % n {
+        power = power * a % n;
+        k += 1;
+    }
+    k
+}
+
+#[test]
+fn test_multiplicative_order() {
+    assert_eq!(multiplicative_order(&BigInt::from(2), &BigInt::from(7)), Some(3.into()));
+    assert_eq!(multiplicative_order(&BigInt::from(3), &BigInt::from(7)), Some(6.into()));
+    assert_eq!(multiplicative_order(&BigInt::from(5), &BigInt::from(1)), Some(1.into()));
+    assert_eq!(multiplicative_order(&BigInt::from(-1), &BigInt::from(7)), Some(2.into()));
+
+    for n in 1..200u64 {
+        for a in 0..n {
+            let order = multiplicative_order(&BigInt::from(a), &BigInt::from(n));
+            if BigInt::from(a).gcd(&BigInt::from(n)) == 1 {
+                assert_eq!(order, Some(order_by_brute_force(a, n).into()), "{} mod {}", a, n);
+            } else {
+                assert_eq!(order, None);
+            }
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_multiplicative_order_zero_n() {
+    multiplicative_order(&BigInt::from(2), &BigInt::from(0));
+}
+
+#[test]
+fn test_primitive_root() {
+    let seven = BigInt::from(7);
+    let root = primitive_root(&seven).expect("7 is prime");
+    assert_eq!(multiplicative_order(&root, &seven), Some(6.into()));
+
+    assert_eq!(primitive_root(&BigInt::from(2)), Some(1.into()));
+    assert_eq!(primitive_root(&BigInt::from(3)), Some(2.into()));
+    assert_eq!(primitive_root(&BigInt::from(41)), Some(6.into()));
+
+    for p in crate::primality::FIRST_100_PRIMES {
+        let p = BigInt::from(*p);
+        let root = primitive_root(&p).expect("a prime");
+        assert_eq!(multiplicative_order(&root, &p), Some(BigInt::from(&p - 1)));
+    }
+
+    // 2**61 - 1, where p - 1 still factors quickly.
+    let mersenne = (BigInt::from(1) << 61) - 1;
+    assert_eq!(primitive_root(&mersenne), Some(37.into()));
+
+    for n in [-7, 0, 1, 8, 561] {
+        assert_eq!(primitive_root(&BigInt::from(n)), None);
+    }
+}
+
+#[test]
+fn test_primes_in_range() {
+    assert_eq!(primes_in_range(&BigInt::from(0), &BigInt::from(10)), [2, 3, 5, 7]);
+    assert_eq!(primes_in_range(&BigInt::from(2), &BigInt::from(2)), [2]);
+    assert_eq!(primes_in_range(&BigInt::from(24), &BigInt::from(28)), [] as [u32; 0]);
+    assert_eq!(primes_in_range(&BigInt::from(10), &BigInt::from(5)), [] as [u32; 0]);
+
+    let expected: Vec<BigInt> = crate::esieve::sieve(10_000)
+        .unwrap()
+        .into_iter()
+        .filter(|p| *p >= 9000)
+        .map(BigInt::from)
+        .collect();
+    assert_eq!(
+        primes_in_range(&BigInt::from(9000), &BigInt::from(10_000)),
+        expected
+    );
+
+    // 10**18 + 3, + 9, + 31 and + 79 are the primes in this range.
+    let lo = BigInt::from(BigInt::u_pow_u(10, 18));
+    let hi = BigInt::from(&lo + 100);
+    let primes = primes_in_range(&lo, &hi);
+    assert_eq!(primes, [3u32, 9, 31, 79].map(|d| BigInt::from(&lo + d)));
+    for p in primes {
+        assert_ne!(p.is_probably_prime(30), IsPrime::No);
+    }
+}
+
+#[test]
+fn test_fermat_quotient() {
+    let quotient = |a: i32, p: u32| fermat_quotient(&BigInt::from(a), &BigInt::from(p));
+    assert_eq!(quotient(2, 3), Some(BigInt::from(1)));
+    assert_eq!(quotient(2, 5), Some(BigInt::from(3)));
+    assert_eq!(quotient(2, 7), Some(BigInt::from(2)));
+    assert_eq!(quotient(3, 5), Some(BigInt::from(1)));
+    assert_eq!(quotient(-1, 7), Some(BigInt::from(0)));
+    // 11 is a Wieferich prime to base 3.
+    assert_eq!(quotient(3, 11), Some(BigInt::from(0)));
+
+    // The Wieferich primes below 10**4.
+    let wieferich: Vec<usize> = crate::esieve::sieve(10_000)
+        .unwrap()
+        .into_iter()
+        .filter(|p| quotient(2, *p as u32) == Some(BigInt::from(0)))
+        .collect();
+    assert_eq!(wieferich, [1093, 3511]);
+
+    // The quotient straight from the definition.
+    for p in crate::primality::FIRST_100_PRIMES.iter().take(30) {
+        for a in 1..20 {
+            if a % p == 0 {
+                assert_eq!(quotient(a as i32, *p), None);
+                continue;
+            }
+            let numerator = BigInt::from(BigInt::u_pow_u(a, p - 1)) - 1;
+            let expected = BigInt::from(&numerator / *p) % *p;
+            assert_eq!(quotient(a as i32, *p), Some(expected), "a = {}, p = {}", a, p);
+        }
+    }
+
+    for p in [0, 1, 4, 9, 561] {
+        assert_eq!(quotient(2, p), None, "p = {}", p);
+    }
+}
+
+#[test]
+fn test_wieferich() {
+    assert!(is_wieferich(&BigInt::from(1093)));
+    assert!(is_wieferich(&BigInt::from(3511)));
+    for p in [0, 1, 2, 3, 5, 7, 1091, 1097, 3527, 1093 * 3511] {
+        assert!(!is_wieferich(&BigInt::from(p)), "p = {}", p);
+    }
+
+    assert_eq!(search_wieferich(5000), [1093, 3511]);
+    assert_eq!(search_wieferich(3510), [1093]);
+    assert_eq!(search_wieferich(1092), Vec::<usize>::new());
+    assert_eq!(search_wieferich(0), Vec::<usize>::new());
+}
+
+#[test]
+fn test_next_prime() {
+    for n in [-5, 0, 1] {
+        assert_eq!(next_prime(&BigInt::from(n)), 2);
+    }
+    assert_eq!(next_prime(&BigInt::from(2)), 3);
+    assert_eq!(next_prime(&BigInt::from(13)), 17);
+    assert_eq!(next_prime(&BigInt::from(1_000_000_000)), 1_000_000_007);
+    for pair in crate::primality::FIRST_100_PRIMES.windows(2) {
+        assert_eq!(next_prime(&BigInt::from(pair[0])), pair[1]);
+        assert_eq!(next_prime(&BigInt::from(pair[1] - 1)), pair[1]);
+    }
+
+    let queried = std::cell::RefCell::new(vec![]);
+    let oracle = |n: &BigInt| {
+        queried.borrow_mut().push(n.clone());
+        MillerRabinOracle.is_prime(n)
+    };
+    assert_eq!(next_prime_with_oracle(&BigInt::from(13), &oracle), 17);
+    assert_eq!(queried.into_inner(), [15, 17].map(BigInt::from));
+}
+
+#[test]
+fn test_decimal_period() {
+    let period = |p: u32| decimal_period(&BigInt::from(p));
+    assert_eq!(period(3), Some(BigInt::from(1)));
+    assert_eq!(period(7), Some(BigInt::from(6)));
+    assert_eq!(period(11), Some(BigInt::from(2)));
+    assert_eq!(period(13), Some(BigInt::from(6)));
+    assert_eq!(period(17), Some(BigInt::from(16)));
+    assert_eq!(period(37), Some(BigInt::from(3)));
+    assert_eq!(period(101), Some(BigInt::from(4)));
+
+    for n in [0, 1, 2, 5, 9, 21, 49] {
+        assert_eq!(period(n), None, "n = {}", n);
+    }
+
+    // The period is where the remainders of the long division of 1 / p first come back to 1.
+    for p in crate::primality::FIRST_100_PRIMES.iter().filter(|p| **p != 2 && **p != 5) {
+        let mut remainder = 10 % p;
+        let mut k = 1;
+        while remainder != 1 {
+            remainder = remainder * 10 % p;
+            k += 1;
+        }
+        assert_eq!(period(*p), Some(BigInt::from(k)), "p = {}", p);
+    }
+}
+
+#[test]
+fn test_cullen_and_woodall_primes() {
+    let cullen: Vec<u32> = (0..200).filter(|n| is_cullen_prime(*n)).collect();
+    assert_eq!(cullen, [1, 141]);
+
+    // 7, 23 and 383 are the smallest Woodall primes.
+    let woodall: Vec<u32> = (0..130).filter(|n| is_woodall_prime(*n)).collect();
+    assert_eq!(woodall, [2, 3, 6, 30, 75, 81, 115, 123]);
+}
+
+#[test]
+fn test_factorial_mod() {
+    let factorial = |n: u32, p: u32| factorial_mod(&BigInt::from(n), &BigInt::from(p));
+    assert_eq!(factorial(5, 7), 1);
+    assert_eq!(factorial(10, 7), 0);
+    assert_eq!(factorial(7, 7), 0);
+    assert_eq!(factorial(0, 7), 1);
+    assert_eq!(factorial(0, 1), 0);
+    assert_eq!(factorial(3, 1000), 6);
+    assert_eq!(factorial(20, 1_000_000_007), 146326063);
+
+    // Wilson's theorem: (n - 1)! = -1 mod n exactly when n is prime.
+    for n in 2..200 {
+        let is_prime = crate::primality::is_prime_u64(n.into());
+        assert_eq!(factorial(n - 1, n) == n - 1, is_prime, "n = {}", n);
+    }
+}
+
+#[test]
+fn test_binomial_mod_prime() {
+    let binomial = |n: u32, k: i32, p: u32| {
+        binomial_mod_prime(&BigInt::from(n), &BigInt::from(k), &BigInt::from(p))
+    };
+    assert_eq!(binomial(10, 3, 7), 1);
+    assert_eq!(binomial(10, 11, 7), 0);
+    assert_eq!(binomial(10, -1, 7), 0);
+    assert_eq!(binomial(0, 0, 2), 1);
+
+    // Against the binomials from Pascal's triangle.
+    let primes = [2, 3, 5, 7, 11, 13, 97];
+    let mut row = vec![BigInt::from(1)];
+    for n in 0..120u32 {
+        for (k, exact) in (0..).zip(&row) {
+            for p in primes {
+                assert_eq!(
+                    binomial(n, k, p),
+                    BigInt::from(exact % p),
+                    "C({}, {}) mod {}",
+                    n,
+                    k,
+                    p
+                );
+            }
+        }
+        let mut next = vec![BigInt::from(1)];
+        next.extend(row.windows(2).map(|pair| BigInt::from(&pair[0] + &pair[1])));
+        next.push(BigInt::from(1));
+        row = next;
+    }
+
+    // C(p - 1, k) = (-1)**k mod p, so with every base 7 digit of n being 6, C(n, k) is -1 raised to
+    // the digit sum of k, which is 136 for 10**40.
+    let n = BigInt::from(BigInt::u_pow_u(7, 60)) - 1;
+    let k = BigInt::from(BigInt::u_pow_u(10, 40));
+    assert_eq!(binomial_mod_prime(&n, &k, &BigInt::from(7)), 1);
+    assert_eq!(binomial_mod_prime(&n, &BigInt::from(&k + 1), &BigInt::from(7)), 6);
+
+    // 2**99 has a 1 where 2**100 has a 0.
+    let n = BigInt::from(BigInt::u_pow_u(2, 100));
+    let k = BigInt::from(BigInt::u_pow_u(2, 99));
+    assert_eq!(binomial_mod_prime(&n, &k, &BigInt::from(2)), 0);
+    assert_eq!(binomial_mod_prime(&n, &n, &BigInt::from(2)), 1);
+}
+
+#[test]
+fn test_quadratic_residues() {
+    assert_eq!(quadratic_residues(&BigInt::from(2)), [1]);
+    assert_eq!(quadratic_residues(&BigInt::from(7)), [1, 2, 4]);
+    assert_eq!(quadratic_residues(&BigInt::from(13)), [1, 3, 4, 9, 10, 12]);
+
+    for p in crate::primality::FIRST_100_PRIMES.iter().skip(1) {
+        let p = BigInt::from(*p);
+        let residues = quadratic_residues(&p);
+        assert_eq!(residues.len(), BigInt::from(&p >> 1));
+        // Each residue has Legendre symbol 1, which for a prime is the Jacobi symbol.
+        for a in residues {
+            assert_eq!(jacobi_symbol(&a, &p), 1);
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_quadratic_residues_below_two() {
+    quadratic_residues(&BigInt::from(1));
+}
+
+#[test]
+fn test_mod_pow_trace() {
+    let (result, trace) = mod_pow_trace(&BigInt::from(3), &BigInt::from(13), &BigInt::from(7));
+    assert_eq!(result, 3);
+    assert_eq!(trace, [1, 3, 2, 6, 1, 1, 3]);
+
+    for (base, exp, modulus) in [(2, 10, 1000), (7, 1, 11), (5, 255, 97), (-3, 5, 11), (4, 3, 1)] {
+        let (base, exp, modulus) = (BigInt::from(base), BigInt::from(exp), BigInt::from(modulus));
+        let (result, trace) = mod_pow_trace(&base, &exp, &modulus);
+        assert_eq!(result, BigInt::from(base.pow_mod_ref(&exp, &modulus).unwrap()));
+        assert_eq!(trace.last(), Some(&result));
+        let steps = exp.significant_bits() + exp.count_ones().unwrap();
+        assert_eq!(trace.len(), steps as usize);
+        assert!(trace.iter().all(|value| *value >= 0 && *value < modulus));
+    }
+
+    let (result, trace) = mod_pow_trace(&BigInt::from(5), &BigInt::new(), &BigInt::from(7));
+    assert_eq!(result, 1);
+    assert!(trace.is_empty());
+}
+
+#[test]
+#[should_panic]
+fn test_mod_pow_trace_negative_exp() {
+    mod_pow_trace(&BigInt::from(2), &BigInt::from(-1), &BigInt::from(7));
+}
@@ -12,20 +12,56 @@
 //!
 //! One of the strongest tests as of 2023 is the Baillie-PSW test. It is a battery of several tests
 //! and no composite number below 2**64 is known to pass. Currently there are no known pseudoprimes.
+//!
+//! Bad input, like a zero base, makes the tests panic as listed under each one's *Panics*. The
+//! `_checked` variants return an [Error] instead, and the tests built on them always check. The
+//! other checks are only made in debug builds, unless the `strict-validation` feature keeps them in
+//! release builds too. Without the checks, bad input gives a meaningless answer.
 
 use std::{
-    cell::OnceCell,
+    collections::{hash_map::RandomState, HashSet},
+    hash::{BuildHasher, Hasher},
     num::NonZeroU32,
-    ops::{Add, Div, ShrAssign},
+    time::{Duration, Instant},
 };
 
+mod montgomery;
 #[cfg(test)]
 mod primality_tests;
 
-use rug::{integer::SmallInteger, ops::DivFrom};
+use montgomery::Montgomery;
+use rug::{
+    integer::{IsPrime, Order},
+    ops::Pow,
+    rand::RandState,
+};
 
 use super::*;
 
+/// Panics with `message` unless `condition` holds. Only checked in debug builds, or with the
+/// `strict-validation` feature.
+macro_rules! check_input {
+    ($condition:expr, $message:literal) => {
+        if cfg!(any(debug_assertions, feature = "strict-validation")) && !$condition {
+            panic!($message);
+        }
+    };
+}
+
+/// Invalid input to one of the primality tests.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The base `a` was zero.
+    ZeroBase,
+    /// The integer `n` under test was zero or negative.
+    NonPositiveN,
+    /// The base is `-1 mod n` for an odd `n`, which passes Fermat's test whether `n` is prime or
+    /// not.
+    BaseGivesNoInfo,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
 /// Fermat's test for primality.
 ///
 /// *This is a probalistic test: primes will always pass, but some composites may also pass. If a
@@ -56,32 +92,75 @@ use super::*;
 /// - `a` is the base, a commonly used value is 2. `a` should preferably be larger than 1 and less
 ///   than `n - 1`. (`a=1` gives no info, and neither `a=n-1` nor `n | a`)
 ///
+/// For odd `n` and `a = -1 mod n` the test passes trivially, since `(-1)**n = -1`. The result is
+/// `true` then, but says nothing about `n`; [fermats_test_checked] reports this as an error.
+///
 /// # Panics
 /// - `a == 0`
 /// - `n < 1`
+///
+/// See [fermats_test_checked] for a version that returns an error instead.
 pub fn fermats_test(n: &BigInt, a: BigInt) -> bool {
+    match fermats_test_checked(n, a) {
+        Ok(passed) => passed,
+        Err(Error::BaseGivesNoInfo) => true,
+        Err(e) => panic!("fermats_test: {:?}", e),
+    }
+}
+
+/// [fermats_test], but bad input gives an [Error] instead of a panic.
+///
+/// # Errors
+/// - [Error::ZeroBase] if `a == 0`
+/// - [Error::NonPositiveN] if `n < 1`
+/// - [Error::BaseGivesNoInfo] if `n` is odd and `a = -1 mod n`, so that any such `n` passes
+pub fn fermats_test_checked(n: &BigInt, a: BigInt) -> Result<bool> {
     // If n is prime, the order of (Z/nZ)* is n - 1
     //  => a**n = a   mod n
 
-    if n == &make_one() {
-        return false;
+    if a.is_zero() {
+        return Err(Error::ZeroBase);
+    }
+    if !n.is_positive() {
+        return Err(Error::NonPositiveN);
+    }
+    if let Some(prime) = handle_small_n(n) {
+        return Ok(prime);
+    }
+    if n.is_odd() && BigInt::from(&a + 1u32).is_divisible(n) {
+        return Err(Error::BaseGivesNoInfo);
     }
-
-    assert!(!a.is_zero());
-    assert!(n.is_positive());
 
     let mut c = a.clone();
     c.pow_mod_mut(n, n)
         .expect("n was negative and an inverse did not exist");
-    c == a.modulo(n)
+    Ok(c == a.modulo(n))
 }
 
-fn make_one() -> SmallInteger {
-    SmallInteger::from(1)
+/// Runs [fermats_test] for each of `bases` in turn, passing only if `n` passes for all of them.
+/// Stops at the first base `n` fails for.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{primality::*, BigInt};
+/// let bases = [2, 3, 5].map(BigInt::from);
+/// assert!(fermat_test_bases(&97.into(), &bases));
+/// assert!(!fermat_test_bases(&91.into(), &bases));
+/// ```
+///
+/// # Panics
+/// The same as [fermats_test], for any base that is reached.
+pub fn fermat_test_bases(n: &BigInt, bases: &[BigInt]) -> bool {
+    // Without this, 1 would pass for an empty list of bases.
+    if let Some(prime) = handle_small_n(n) {
+        return prime;
+    }
+    bases.iter().all(|a| fermats_test(n, a.clone()))
 }
 
-fn make_two() -> SmallInteger {
-    SmallInteger::from(2)
+fn make_two() -> BigInt {
+    BigInt::from(2)
 }
 
 /// The Miller-Rabin primality test.
@@ -98,31 +177,76 @@ fn make_two() -> SmallInteger {
 ///
 /// Conversely, a given `a` is called a liar if `n` passes for `a`.
 ///
+/// Before exponentiating, `n` is trial divided by the primes up to 97, and fails straight away if
+/// one of them is a proper factor. Such composites fail for every base, even the strong liars of
+/// the bare test, such as 3 for `121`. [pseudoprime_class] skips this step.
+///
 /// # Arguments
 /// - `n` is the integer we wish to test.
 /// - `a` is the base, a commonly used value is 2. `a` should preferably be larger than 1 and less
 ///   than `n - 1`. (`a=1` gives no info, and neither `a=n-1` nor `n | a`)
 ///
+/// The test works with any [PrimalityInt], so native integers like `u64` can skip the bignum
+/// overhead.
+///
 /// # Panics
 /// - `b == 0`
 /// - `n < 1`
-pub fn miller_rabin_test(n: &BigInt, mut a: BigInt) -> bool {
-    assert!(!a.is_zero());
-    assert!(n.is_positive());
+///
+/// See [miller_rabin_test_checked] for a version that returns an error instead.
+pub fn miller_rabin_test<T: PrimalityInt>(n: &T, a: T) -> bool {
+    miller_rabin_test_checked(n, a).unwrap_or_else(|e| panic!("miller_rabin_test: {:?}", e))
+}
 
-    // TODO: probably much quicker way to check this? `is_even`?
-    if n == &make_two() {
-        return true;
+/// [miller_rabin_test], but bad input gives an [Error] instead of a panic.
+///
+/// # Errors
+/// - [Error::ZeroBase] if `a == 0`
+/// - [Error::NonPositiveN] if `n < 1`
+pub fn miller_rabin_test_checked<T: PrimalityInt>(n: &T, a: T) -> Result<bool> {
+    if a.is_zero() {
+        return Err(Error::ZeroBase);
+    }
+    if !n.is_positive() {
+        return Err(Error::NonPositiveN);
+    }
+
+    if let Some(prime) = handle_small_n(n) {
+        return Ok(prime);
     }
 
+    // Cheaper than the exponentiation, and catches most composites.
+    if has_small_factor(n) {
+        return Ok(false);
+    }
+
+    Ok(strong_probable_prime_test(n, a))
+}
+
+/// The primes up to 97, which [miller_rabin_test] trial divides by.
+const MILLER_RABIN_TRIAL_PRIMES: &[u32] = FIRST_100_PRIMES.split_at(25).0;
+
+/// Whether one of [MILLER_RABIN_TRIAL_PRIMES] divides `n` without being `n` itself.
+fn has_small_factor<T: PrimalityInt>(n: &T) -> bool {
+    MILLER_RABIN_TRIAL_PRIMES
+        .iter()
+        .map(|p| T::from_u32(*p))
+        .any(|p| *n != p && n.modulo(&p).is_zero())
+}
+
+/// The Miller-Rabin test proper, without the input checks and trial division of
+/// [miller_rabin_test_checked]. Needs `a != 0` and `n > 2`.
+fn strong_probable_prime_test<T: PrimalityInt>(n: &T, a: T) -> bool {
+    let one = T::from_u32(1);
+
     // Factor n - 1 == 2**k q
-    let mut q = BigInt::from(n - 1);
-    let mut k = BigInt::new();
+    let mut q = n.sub_one();
+    let mut k = 0u32;
 
     loop {
-        if q > 1 && q.is_even() {
+        if q > one && q.is_even() {
             k += 1;
-            q.shr_assign(1);
+            q = q.halve();
         } else {
             break;
         }
@@ -140,52 +264,1321 @@ pub fn miller_rabin_test(n: &BigInt, mut a: BigInt) -> bool {
     // Hence if n is actually prime,
     //  either one number number a**(2**i q) mod n (i=0,...,k-1) is -1 and we get 1 by squaring
     //  or a**(q) == 1
-    let minus_one = BigInt::from(n - 1);
+    let minus_one = n.sub_one();
 
-    a.pow_mod_mut(&q, &n).expect("Should have a result");
+    let mut a = a.pow_mod(&q, n);
 
-    if &a == &make_one() {
+    if a == one {
         return true;
     }
 
-    let mut i = BigInt::from(0);
-    while i < k {
+    for _ in 0..k {
         if a == minus_one {
             return true;
         }
-        a.pow_mod_mut(&make_two(), &n)
-            .expect("Should have a result");
+        a.square_mod_mut(n);
+    }
+
+    false
+}
+
+/// The Solovay-Strassen primality test.
+///
+/// *This is a probalistic test: primes will always pass, but some composites may also pass. If a
+/// number fails, it is guaranteed composite.*
+///
+/// By Euler's criterion, for an odd prime `n` and `a` coprime to it
+/// ```text
+///     a**((n - 1) / 2) = (a/n)   mod n
+/// ```
+/// where `(a/n)` is the Jacobi symbol. At least half of the bases expose any odd composite, which
+/// makes this stronger than Fermat's test but weaker than Miller-Rabin.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// assert!(solovay_strassen_test(&97.into(), 2.into()));
+/// // 341 fools Fermat's test with base 2, but not this one.
+/// assert!(!solovay_strassen_test(&341.into(), 2.into()));
+/// ```
+///
+/// # Panics
+/// - `a == 0`
+/// - `n < 1`
+pub fn solovay_strassen_test(n: &BigInt, a: BigInt) -> bool {
+    check_input!(!a.is_zero(), "a must not be zero");
+    check_input!(n.is_positive(), "n must be positive");
 
-        i += 1;
+    if let Some(prime) = handle_small_n(n) {
+        return prime;
+    }
+    if n.is_even() {
+        return false;
     }
 
-    return false;
+    let expected = match a.jacobi(n) {
+        // a shares a factor with n.
+        0 => return false,
+        1 => BigInt::from(1),
+        _ => BigInt::from(n - 1),
+    };
+    let exponent = BigInt::from(n >> 1);
+    let power = BigInt::from(a.pow_mod_ref(&exponent, n).expect("exponent is non-negative"));
+    power == expected
 }
 
-struct PrimalityTestOptions {
-    pub rounds: NonZeroU32,
+/// How strongly a composite `n` imitates a prime for a base `a`, see [pseudoprime_class].
+///
+/// Each kind of pseudoprime is also one of the kinds before it, so the variants are ordered from
+/// weakest to strongest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PseudoprimeClass {
+    /// `n` is prime, or below 2, so the question doesn't apply.
+    NotComposite,
+    /// `n` fails Fermat's test, so `a` proves it composite.
+    NotPseudoprime,
+    /// `n` passes [fermats_test] but not [solovay_strassen_test].
+    Fermat,
+    /// `n` passes [solovay_strassen_test] but not [miller_rabin_test].
+    Euler,
+    /// `n` passes [miller_rabin_test].
+    Strong,
 }
 
-impl PrimalityTestOptions {
-    pub fn suggested(a: &BigInt) -> Self {
-        todo!()
+/// Classifies `n` as a Fermat, Euler or strong pseudoprime to base `a`, whichever is the strongest
+/// it qualifies as.
+///
+/// Every strong pseudoprime is an Euler pseudoprime, and every Euler pseudoprime a Fermat
+/// pseudoprime. Euler and strong pseudoprimes are only defined for odd `n`, so even composites are
+/// at most Fermat pseudoprimes. Primality is decided by GMP's probabilistic test.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// assert_eq!(pseudoprime_class(&2047.into(), &2.into()), PseudoprimeClass::Strong);
+/// assert_eq!(pseudoprime_class(&561.into(), &2.into()), PseudoprimeClass::Euler);
+/// assert_eq!(pseudoprime_class(&341.into(), &2.into()), PseudoprimeClass::Fermat);
+/// ```
+///
+/// # Panics
+/// - `a == 0`
+pub fn pseudoprime_class(n: &BigInt, a: &BigInt) -> PseudoprimeClass {
+    check_input!(!a.is_zero(), "a must not be zero");
+
+    if *n < 2 || n.is_probably_prime(30) != IsPrime::No {
+        return PseudoprimeClass::NotComposite;
+    }
+    if !fermats_test(n, a.clone()) {
+        return PseudoprimeClass::NotPseudoprime;
+    }
+    if n.is_even() || !solovay_strassen_test(n, a.clone()) {
+        return PseudoprimeClass::Fermat;
     }
+    // Strong pseudoprimes can have small factors, like 121 for base 3, so no trial division here.
+    if !strong_probable_prime_test(n, a.clone()) {
+        return PseudoprimeClass::Euler;
+    }
+    PseudoprimeClass::Strong
 }
 
-/// Uses a combination of Fermat's and Miller-Rabin to test whether an integer `a` is a likely
-/// prime. If the test fails, the integer is guaranteed composite. If the test succeeds, it is with
-/// high likelihood a prime.
-fn probabilistic_primality_test(n: &BigInt) -> Primality {
-    todo!()
+/// What a round of Miller-Rabin revealed about `n`, see [miller_rabin_evidence].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MrEvidence {
+    /// `n` passed for the base.
+    ProbablePrime,
+    /// `n` failed for the base, which proves it composite without giving away a factor.
+    CompositeByWitness(BigInt),
+    /// A nontrivial factor of `n` turned up along the way.
+    CompositeByFactor(BigInt),
 }
 
-pub enum Primality {
-    Composite,
-    ProbablyPrime,
-    Prime,
+/// [miller_rabin_test] for [BigInt], but reporting why `n` failed rather than just that it did.
+///
+/// A factor can show up in two ways. If `gcd(a, n)` is nontrivial it is a factor. Otherwise, if the
+/// squarings reach 1 from some `x != +/-1`, then `x` is a square root of 1 other than the obvious
+/// ones, so `n | (x - 1)(x + 1)` without dividing either, and `gcd(x - 1, n)` is a factor. Even
+/// `n` gives the factor 2 straight away. Any other failure makes `a` a witness.
+///
+/// As with [miller_rabin_test], a base divisible by `n` makes `n` fail even when it is prime. Below
+/// 2, `n` isn't prime and `a` is reported as the witness.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// assert_eq!(miller_rabin_evidence(&97.into(), 2.into()), MrEvidence::ProbablePrime);
+/// assert_eq!(
+///     miller_rabin_evidence(&15.into(), 3.into()),
+///     MrEvidence::CompositeByFactor(3.into())
+/// );
+/// // 2**35 squares to 1 mod 561 via 67, and gcd(66, 561) = 33.
+/// assert_eq!(
+///     miller_rabin_evidence(&561.into(), 2.into()),
+///     MrEvidence::CompositeByFactor(33.into())
+/// );
+/// ```
+///
+/// # Panics
+/// - `a == 0`
+pub fn miller_rabin_evidence(n: &BigInt, a: BigInt) -> MrEvidence {
+    check_input!(!a.is_zero(), "a must not be zero");
+
+    if let Some(prime) = handle_small_n(n) {
+        return if prime {
+            MrEvidence::ProbablePrime
+        } else {
+            MrEvidence::CompositeByWitness(a)
+        };
+    }
+    if n.is_even() {
+        return MrEvidence::CompositeByFactor(make_two());
+    }
+
+    let divisor = BigInt::from(a.gcd_ref(n));
+    if divisor != 1 && divisor != *n {
+        return MrEvidence::CompositeByFactor(divisor);
+    }
+
+    // n - 1 == 2**k q
+    let minus_one = BigInt::from(n - 1);
+    let k = minus_one
+        .find_one(0)
+        .expect("n - 1 is positive and has a set bit");
+    let q = BigInt::from(&minus_one >> k);
+
+    let mut x = BigInt::from(a.pow_mod_ref(&q, n).expect("q is non-negative"));
+    if x == 1 {
+        return MrEvidence::ProbablePrime;
+    }
+
+    for _ in 0..k {
+        if x == minus_one {
+            return MrEvidence::ProbablePrime;
+        }
+        let root = x.clone();
+        x.square_mod_mut(n);
+        if x == 1 {
+            return MrEvidence::CompositeByFactor(BigInt::from(root - 1u32).gcd(n));
+        }
+    }
+
+    MrEvidence::CompositeByWitness(a)
+}
+
+/// Settles `n < 2`, which isn't prime, and `n = 2`, which is, so that every test agrees on them.
+/// The tests call this once their input checks have passed. Returns `None` for `n > 2`.
+fn handle_small_n<T: PrimalityInt>(n: &T) -> Option<bool> {
+    let two = T::from_u32(2);
+    if *n < two {
+        Some(false)
+    } else if *n == two {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// The integer operations needed by the generic primality tests, such as [miller_rabin_test].
+///
+/// Implemented for [BigInt] and for `u64`, the latter using `u128` for intermediate products so
+/// nothing overflows.
+pub trait PrimalityInt: Clone + PartialOrd {
+    fn from_u32(value: u32) -> Self;
+    fn is_zero(&self) -> bool;
+    fn is_positive(&self) -> bool;
+    fn is_even(&self) -> bool;
+    /// `self - 1`
+    fn sub_one(&self) -> Self;
+    /// `self / 2`, rounding down.
+    fn halve(&self) -> Self;
+    /// The non-negative remainder of `self / modulus`.
+    fn modulo(&self, modulus: &Self) -> Self;
+    /// `self**exponent mod modulus`, for non-negative `exponent` and positive `modulus`.
+    fn pow_mod(&self, exponent: &Self, modulus: &Self) -> Self;
+    /// Replaces `self` with `self**2 mod modulus`, for positive `modulus`.
+    fn square_mod_mut(&mut self, modulus: &Self);
+}
+
+impl PrimalityInt for BigInt {
+    fn from_u32(value: u32) -> Self {
+        BigInt::from(value)
+    }
+
+    fn is_zero(&self) -> bool {
+        BigInt::is_zero(self)
+    }
+
+    fn is_positive(&self) -> bool {
+        BigInt::is_positive(self)
+    }
+
+    fn is_even(&self) -> bool {
+        BigInt::is_even(self)
+    }
+
+    fn sub_one(&self) -> Self {
+        BigInt::from(self - 1)
+    }
+
+    fn halve(&self) -> Self {
+        BigInt::from(self >> 1)
+    }
+
+    fn modulo(&self, modulus: &Self) -> Self {
+        BigInt::from(self.modulo_ref(modulus))
+    }
+
+    fn pow_mod(&self, exponent: &Self, modulus: &Self) -> Self {
+        BigInt::from(
+            self.pow_mod_ref(exponent, modulus)
+                .expect("exponent should be non-negative"),
+        )
+    }
+
+    fn square_mod_mut(&mut self, modulus: &Self) {
+        // Squaring in place reuses the limbs, where pow_mod would need an exponent and a result.
+        self.square_mut();
+        self.modulo_mut(modulus);
+    }
+}
+
+impl PrimalityInt for u64 {
+    fn from_u32(value: u32) -> Self {
+        value.into()
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+
+    fn is_positive(&self) -> bool {
+        *self > 0
+    }
+
+    fn is_even(&self) -> bool {
+        self.is_multiple_of(2)
+    }
+
+    fn sub_one(&self) -> Self {
+        self - 1
+    }
+
+    fn halve(&self) -> Self {
+        self >> 1
+    }
+
+    fn modulo(&self, modulus: &Self) -> Self {
+        self % modulus
+    }
+
+    fn pow_mod(&self, exponent: &Self, modulus: &Self) -> Self {
+        mod_pow_u64(*self, *exponent, *modulus)
+    }
+
+    fn square_mod_mut(&mut self, modulus: &Self) {
+        *self = ((*self as u128 * *self as u128) % *modulus as u128) as u64;
+    }
+}
+
+/// Computes `base**exponent mod modulus` in native arithmetic.
+///
+/// Odd moduli, which is what the primality tests use, go through Montgomery multiplication. Even
+/// moduli fall back to plain `u128` products and divisions.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// assert_eq!(mod_pow_u64(2, 10, 1000), 24);
+/// assert_eq!(mod_pow_u64(3, u64::MAX, u64::MAX - 58), 17268082312041408519);
+/// ```
+///
+/// # Panics
+/// - `modulus == 0`
+pub fn mod_pow_u64(base: u64, exponent: u64, modulus: u64) -> u64 {
+    check_input!(modulus != 0, "modulus must not be zero");
+
+    if modulus == 1 {
+        return 0;
+    }
+
+    if modulus % 2 == 1 {
+        let montgomery = Montgomery::new(modulus);
+        let base = montgomery.to_montgomery(base % modulus);
+        return montgomery.to_normal(montgomery.mont_pow(base, exponent));
+    }
+
+    let modulus = modulus as u128;
+    let mut base = base as u128 % modulus;
+    let mut exponent = exponent;
+    let mut result = 1;
+
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base % modulus;
+        }
+        base = base * base % modulus;
+        exponent >>= 1;
+    }
+
+    result as u64
+}
+
+/// Miller-Rabin witnesses that together are conclusive for every `u64`, found by Jim Sinclair.
+const U64_MILLER_RABIN_BASES: &[u64] = &[2, 325, 9375, 28178, 450775, 9780504, 1795265022];
+
+/// Settles `n` by trial division with [FIRST_100_PRIMES] where that is enough, so heavier tests can
+/// be skipped.
+///
+/// Returns `Some(true)` if `n` is composite, which here includes `n <= 1` as they aren't prime
+/// either. Returns `Some(false)` if `n` is one of the primes, or has no factor among them and is
+/// below the square of the largest, 541. Otherwise returns `None`, and `n` needs a real test.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// assert_eq!(quick_composite_check(&1_000_000.into()), Some(true));
+/// assert_eq!(quick_composite_check(&97.into()), Some(false));
+/// assert_eq!(quick_composite_check(&1_000_000_007.into()), None);
+/// ```
+pub fn quick_composite_check(n: &BigInt) -> Option<bool> {
+    if *n <= 1 {
+        return Some(true);
+    }
+    for p in FIRST_100_PRIMES {
+        if *n == *p {
+            return Some(false);
+        }
+        if n.is_divisible_u(*p) {
+            return Some(true);
+        }
+    }
+
+    let largest = u64::from(*FIRST_100_PRIMES.last().unwrap());
+    (*n < largest * largest).then_some(false)
+}
+
+/// Deterministic primality test for `u64`, using native arithmetic only.
+///
+/// Small prime factors are caught by trial division, the rest is Miller-Rabin with a fixed set of
+/// bases known to expose every composite below `2**64`. Unlike the probabilistic tests, the answer
+/// is definite.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// assert!(is_prime_u64(2_305_843_009_213_693_951));
+/// assert!(!is_prime_u64(3_215_031_751));
+/// ```
+pub fn is_prime_u64(n: u64) -> bool {
+    if let Some(prime) = handle_small_n(&n) {
+        return prime;
+    }
+
+    for p in FIRST_100_PRIMES.iter().take(12).map(|p| *p as u64) {
+        if n == p {
+            return true;
+        }
+        if n.is_multiple_of(p) {
+            return false;
+        }
+    }
+
+    let montgomery = Montgomery::new(n);
+    U64_MILLER_RABIN_BASES
+        .iter()
+        .map(|a| a % n)
+        // A base divisible by n carries no information, n is coprime to the others.
+        .filter(|a| *a != 0)
+        .all(|a| miller_rabin_montgomery(&montgomery, n, a))
+}
+
+/// [miller_rabin_test] for an odd `n > 2`, staying in Montgomery form between the squarings.
+fn miller_rabin_montgomery(montgomery: &Montgomery, n: u64, a: u64) -> bool {
+    // n - 1 == 2**k q
+    let k = (n - 1).trailing_zeros();
+    let q = (n - 1) >> k;
+
+    let one = montgomery.to_montgomery(1);
+    let minus_one = montgomery.to_montgomery(n - 1);
+
+    let mut a = montgomery.mont_pow(montgomery.to_montgomery(a), q);
+    if a == one {
+        return true;
+    }
+
+    for _ in 0..k {
+        if a == minus_one {
+            return true;
+        }
+        a = montgomery.mont_mul(a, a);
+    }
+
+    false
+}
+
+/// Collects the Fermat liars for a composite `n`: the bases `a` in `[2, n - 2]` for which `n`
+/// passes [fermats_test] anyway.
+///
+/// Since [fermats_test] checks `a**n = a mod n`, bases sharing a factor with `n` can be liars too.
+/// For a Carmichael number every base in the range is a liar. A prime has no liars, so the result
+/// is empty.
+///
+/// Every base is tried, so this is only feasible for small `n`, roughly below `10**6`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{primality::*, BigInt};
+/// // 4 and 11 are coprime to 15, the others share a factor with it.
+/// let liars: Vec<BigInt> = [4, 5, 6, 9, 10, 11].map(BigInt::from).into();
+/// assert_eq!(fermat_liars(&15.into()), liars);
+/// ```
+pub fn fermat_liars(n: &BigInt) -> Vec<BigInt> {
+    if test_with_report(n).primality != Primality::Composite {
+        return vec![];
+    }
+
+    let mut liars = vec![];
+    let mut a = make_two();
+    let last = BigInt::from(n - 2);
+    while a <= last {
+        if fermats_test(n, a.clone()) {
+            liars.push(a.clone());
+        }
+        a += 1;
+    }
+    liars
+}
+
+/// Collects the Fermat witnesses for `n`: the bases `a` in `[2, min(n - 2, limit)]` for which
+/// [fermats_test] fails, proving `n` composite. The complement of [fermat_liars] in that range.
+///
+/// Since [fermats_test] checks `a**n = a mod n` rather than `a**(n - 1) = 1 mod n`, a base sharing
+/// a factor with `n` isn't automatically a witness. A Carmichael number satisfies `a**n = a mod n`
+/// for every `a`, so it has no witnesses at all, and neither does a prime.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{primality::*, BigInt};
+/// let witnesses: Vec<BigInt> = [2, 3, 7, 8, 12, 13].map(BigInt::from).into();
+/// assert_eq!(fermat_witnesses(&15.into(), 100), witnesses);
+/// assert!(fermat_witnesses(&561.into(), 1000).is_empty());
+/// ```
+pub fn fermat_witnesses(n: &BigInt, limit: usize) -> Vec<BigInt> {
+    let mut witnesses = vec![];
+    let mut a = make_two();
+    let last = BigInt::from(n - 2).min(BigInt::from(limit));
+    while a <= last {
+        if !fermats_test(n, a.clone()) {
+            witnesses.push(a.clone());
+        }
+        a += 1;
+    }
+    witnesses
+}
+
+/// Random distinct bases from `[2, n - 2]` for repeated rounds of a primality test.
+///
+/// Testing a base twice tells nothing new, so each base is drawn uniformly from the ones not yet
+/// returned. For small `n` the iterator ends once every base in the range has been returned.
+///
+/// [RandomBases::new] seeds the generator from the OS, so someone picking `n` can't know the bases
+/// in advance. [RandomBases::with_seed] gives the same bases for the same seed.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// let mut bases: Vec<_> = RandomBases::new(&7.into()).collect();
+/// bases.sort();
+/// assert_eq!(bases, [2, 3, 4, 5]);
+/// ```
+pub struct RandomBases {
+    rand: RandState<'static>,
+    /// The number of bases, `n - 3`.
+    range: BigInt,
+    tried: HashSet<BigInt>,
 }
 
-pub const FIRST_100_PRIMES: &'static [u32] = &[
+impl RandomBases {
+    /// Bases for testing `n`, different each time. There are none when `n < 4`.
+    pub fn new(n: &BigInt) -> Self {
+        // std keys each RandomState with random bits from the OS, which is all that is needed here.
+        Self::with_seed(n, RandomState::new().build_hasher().finish())
+    }
+
+    /// Bases for testing `n`, the same ones for the same `seed`. There are none when `n < 4`.
+    pub fn with_seed(n: &BigInt, seed: u64) -> Self {
+        let range = if *n < 4 {
+            BigInt::new()
+        } else {
+            BigInt::from(n - 3)
+        };
+        let mut rand = RandState::new();
+        rand.seed(&BigInt::from(seed));
+        Self {
+            rand,
+            range,
+            tried: HashSet::new(),
+        }
+    }
+}
+
+impl Iterator for RandomBases {
+    type Item = BigInt;
+
+    fn next(&mut self) -> Option<BigInt> {
+        if self.range <= self.tried.len() {
+            return None;
+        }
+        loop {
+            // Bases are drawn as 2 + [0, n - 3).
+            let a: BigInt = BigInt::from(self.range.random_below_ref(&mut self.rand)) + 2;
+            if self.tried.insert(a.clone()) {
+                return Some(a);
+            }
+        }
+    }
+}
+
+/// Runs `rounds` rounds of [miller_rabin_test] with distinct bases from [RandomBases].
+///
+/// *This is a probalistic test: a composite survives each round with probability at most 1/4, so
+/// the chance of a composite passing is at most `4**-rounds`.*
+///
+/// A base sharing a factor with `n` proves it composite by the gcd alone, without the
+/// exponentiation. If `n` is small enough that there are fewer than `rounds` bases, each is tried
+/// once and the answer is exact.
+///
+/// The bases are drawn afresh on every call, so a composite built to pass a known set of bases
+/// has no better odds than any other.
+///
+/// # Example
+///
+/// ```
+/// use std::num::NonZeroU32;
+/// use fermats_kitchen::primality::*;
+///
+/// let rounds = NonZeroU32::new(20).unwrap();
+/// assert!(miller_rabin_rounds(&1_000_000_007.into(), rounds));
+/// assert!(!miller_rabin_rounds(&561.into(), rounds));
+/// ```
+pub fn miller_rabin_rounds(n: &BigInt, rounds: NonZeroU32) -> bool {
+    if let Some(prime) = handle_small_n(n) {
+        return prime;
+    }
+    // With no bases to try, 3 passes below.
+    if n.is_even() {
+        return false;
+    }
+
+    RandomBases::new(n)
+        .take(rounds.get() as usize)
+        .all(|a| number_theory::are_coprime(&a, n) && miller_rabin_test(n, a))
+}
+
+/// What [miller_rabin_confidence] found, and how much testing it took.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MrOutcome {
+    /// `n` failed round `failed_at_round`, counting from 1, for base `witness`. Round 0 means `n`
+    /// was settled before any round was run, being below 2 or even, and the witness is then 2.
+    Composite { failed_at_round: u32, witness: BigInt },
+    /// `n` passed all `rounds_passed` rounds. A composite gets this far with probability at most
+    /// `error_bound`, which is 0 when every possible base was tried.
+    ProbablePrime { rounds_passed: u32, error_bound: f64 },
+}
+
+/// [miller_rabin_rounds], but reporting how much testing actually happened.
+///
+/// *This is a probalistic test, with the same odds as [miller_rabin_rounds]. The bases are drawn
+/// afresh on every call, so for a composite the two may disagree on rare occasions.*
+///
+/// # Example
+///
+/// ```
+/// use std::num::NonZeroU32;
+/// use fermats_kitchen::primality::*;
+///
+/// let rounds = NonZeroU32::new(10).unwrap();
+/// assert_eq!(
+///     miller_rabin_confidence(&1_000_000_007.into(), rounds),
+///     MrOutcome::ProbablePrime { rounds_passed: 10, error_bound: 0.25f64.powi(10) }
+/// );
+/// assert!(matches!(
+///     miller_rabin_confidence(&561.into(), rounds),
+///     MrOutcome::Composite { .. }
+/// ));
+/// ```
+pub fn miller_rabin_confidence(n: &BigInt, rounds: NonZeroU32) -> MrOutcome {
+    let settled = handle_small_n(n).or_else(|| n.is_even().then_some(false));
+    match settled {
+        Some(true) => {
+            return MrOutcome::ProbablePrime {
+                rounds_passed: 0,
+                error_bound: 0.0,
+            }
+        }
+        Some(false) => {
+            return MrOutcome::Composite {
+                failed_at_round: 0,
+                witness: make_two(),
+            }
+        }
+        // 3 has no bases to try, so it passes with none run below.
+        None => {}
+    }
+
+    let mut rounds_passed = 0;
+    for a in RandomBases::new(n).take(rounds.get() as usize) {
+        if !(number_theory::are_coprime(&a, n) && miller_rabin_test(n, a.clone())) {
+            return MrOutcome::Composite {
+                failed_at_round: rounds_passed + 1,
+                witness: a,
+            };
+        }
+        rounds_passed += 1;
+    }
+
+    // Running out of bases early means every one of them was tried, so the answer is exact.
+    let error_bound = if rounds_passed < rounds.get() {
+        0.0
+    } else {
+        0.25f64.powf(rounds_passed as f64)
+    };
+    MrOutcome::ProbablePrime {
+        rounds_passed,
+        error_bound,
+    }
+}
+
+/// Adds [PrimeFilterExt::primes] to iterators over [BigInt].
+pub trait PrimeFilterExt: Iterator<Item = BigInt> + Sized {
+    /// Keeps only the elements that pass 25 rounds of [miller_rabin_rounds].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fermats_kitchen::{primality::*, BigInt};
+    /// let primes: Vec<BigInt> = (1..20).map(BigInt::from).primes().collect();
+    /// assert_eq!(primes, [2, 3, 5, 7, 11, 13, 17, 19]);
+    /// ```
+    fn primes(self) -> impl Iterator<Item = BigInt> {
+        let rounds = NonZeroU32::new(25).unwrap();
+        self.filter(move |n| miller_rabin_rounds(n, rounds))
+    }
+}
+
+impl<I: Iterator<Item = BigInt>> PrimeFilterExt for I {}
+
+/// Tests `n` with up to 25 rounds of Miller-Rabin, like [miller_rabin_rounds], but gives up once
+/// `budget` has run out.
+///
+/// Returns `Some(Primality::Composite)` as soon as a round fails, and
+/// `Some(Primality::ProbablyPrime)` if every round that fit in the budget passed. If the budget
+/// ran out before the first round finished, `n` may be anything and `None` is returned. Integers
+/// below 4 and even integers are settled without any rounds.
+///
+/// The budget is checked between rounds, so a single slow round can overrun it.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use fermats_kitchen::primality::*;
+///
+/// let budget = Duration::from_secs(1);
+/// assert_eq!(is_prime_timeout(&97.into(), budget), Some(Primality::ProbablyPrime));
+/// assert_eq!(is_prime_timeout(&561.into(), budget), Some(Primality::Composite));
+/// assert_eq!(is_prime_timeout(&97.into(), Duration::ZERO), None);
+/// ```
+pub fn is_prime_timeout(n: &BigInt, budget: Duration) -> Option<Primality> {
+    let start = Instant::now();
+
+    if *n < 4 {
+        return Some(if *n > 1 {
+            Primality::Prime
+        } else {
+            Primality::Composite
+        });
+    }
+    if n.is_even() {
+        return Some(Primality::Composite);
+    }
+
+    for (round, a) in RandomBases::new(n).take(25).enumerate() {
+        if start.elapsed() >= budget {
+            return (round > 0).then_some(Primality::ProbablyPrime);
+        }
+        if !miller_rabin_test(n, a) {
+            return Some(Primality::Composite);
+        }
+    }
+    Some(Primality::ProbablyPrime)
+}
+
+/// Tests the non-negative integer whose digits are `bytes` with 25 rounds of [miller_rabin_rounds],
+/// for numbers held in byte buffers as in cryptographic code. `order` says which end of `bytes` is
+/// the most significant, see [BigInt::from_digits].
+///
+/// *This is a probalistic test, with the same odds as [miller_rabin_rounds].*
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// use rug::integer::Order;
+///
+/// // 65537
+/// assert!(is_prime_bytes(&[0x01, 0x00, 0x01], Order::Msf));
+/// assert!(!is_prime_bytes(&[0x01, 0x00, 0x00], Order::Msf));
+/// ```
+pub fn is_prime_bytes(bytes: &[u8], order: Order) -> bool {
+    let n = BigInt::from_digits(bytes, order);
+    miller_rabin_rounds(&n, NonZeroU32::new(25).unwrap())
+}
+
+/// Checks whether `n` is a Carmichael number, i.e. a composite that passes Fermat's test for every
+/// base coprime to it.
+///
+/// Uses Korselt's criterion: `n` is a Carmichael number exactly when it is composite, square-free
+/// and `p - 1 | n - 1` for every prime `p | n`. The prime factors are found by trial division, so
+/// this is only practical while `sqrt(n)` is small enough to walk, say `n < 10**14`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// assert!(is_carmichael(&561.into()));
+/// assert!(!is_carmichael(&563.into()));
+/// ```
+pub fn is_carmichael(n: &BigInt) -> bool {
+    // All Carmichael numbers are odd, and they pass Fermat's test for every coprime base, so this
+    // weeds out most composites before the slow part.
+    if *n < 3 || n.is_even() || !fermats_test(n, make_two()) {
+        return false;
+    }
+
+    let n_minus_one = BigInt::from(n - 1);
+    let mut rest = n.clone();
+    let mut prime_factors = 0;
+    let mut p = BigInt::from(3);
+
+    while BigInt::from(p.square_ref()) <= rest {
+        if rest.is_divisible(&p) {
+            rest /= &p;
+            if rest.is_divisible(&p) || !n_minus_one.is_divisible(&BigInt::from(&p - 1)) {
+                return false;
+            }
+            prime_factors += 1;
+        }
+        p += 2;
+    }
+
+    // Whatever is left is 1 or a prime factor, which might be n itself.
+    if rest > 1 && rest != *n {
+        if !n_minus_one.is_divisible(&BigInt::from(&rest - 1)) {
+            return false;
+        }
+        prime_factors += 1;
+    }
+
+    prime_factors > 1
+}
+
+/// Proves `n` prime with the Pocklington-Lehmer criterion, given a partial factorization of
+/// `n - 1`.
+///
+/// Write `n - 1 = F R` where `F` is the product of `factored_part`. If `F > sqrt(n)` and for every
+/// prime `q | F` there is a base `a` with `a**(n - 1) = 1 mod n` and
+/// `gcd(a**((n - 1) / q) - 1, n) = 1`, then `n` is prime. Unlike the probabilistic tests, a `true`
+/// here is a proof, provided every `q` in `factored_part` really is prime.
+///
+/// Returns `false` if `n` is composite, but also if the criterion can't be met: `F` doesn't divide
+/// `n - 1`, `F` is too small, or no base among [FIRST_100_PRIMES] works for some `q`. `n = 2` is
+/// the one exception, it is prime whatever `factored_part` says.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{primality::*, BigInt};
+/// // 998244353 - 1 = 2**23 * 7 * 17, and 2**23 alone exceeds sqrt(998244353).
+/// let factored_part = [(BigInt::from(2), 23)];
+/// assert!(pocklington_test(&998_244_353.into(), &factored_part));
+/// ```
+pub fn pocklington_test(n: &BigInt, factored_part: &[(BigInt, u32)]) -> bool {
+    if let Some(prime) = handle_small_n(n) {
+        return prime;
+    }
+
+    let n_minus_one = BigInt::from(n - 1);
+    let f = factored_part
+        .iter()
+        .fold(BigInt::from(1), |f, (q, e)| f * BigInt::from(q.pow(*e)));
+    if !n_minus_one.is_divisible(&f) || BigInt::from(f.square_ref()) <= *n {
+        return false;
+    }
+
+    let pow_mod = |a: &BigInt, exponent: &BigInt| {
+        BigInt::from(
+            a.pow_mod_ref(exponent, n)
+                .expect("exponent is non-negative"),
+        )
+    };
+
+    let bases: Vec<BigInt> = FIRST_100_PRIMES
+        .iter()
+        .map(|a| BigInt::from(*a))
+        .take_while(|a| a < n)
+        .collect();
+    // A base failing Fermat's test shows n is composite, no matter which q it was tried for.
+    for a in &bases {
+        if pow_mod(a, &n_minus_one) != 1 {
+            return false;
+        }
+    }
+
+    factored_part.iter().all(|(q, _)| {
+        let exponent = BigInt::from(&n_minus_one / q);
+        bases.iter().any(|a| {
+            let power = pow_mod(a, &exponent) - 1u32;
+            BigInt::from(power.gcd_ref(n)) == 1
+        })
+    })
+}
+
+/// Deterministically tests whether the Proth number `N = k 2**n + 1` is prime.
+///
+/// Proth's theorem says `N` is prime exactly when `a**((N - 1) / 2) = -1 mod N` for some `a`. If
+/// `a` is a quadratic non-residue mod `N`, i.e. the Jacobi symbol `(a/N)` is `-1`, this holds for
+/// every prime `N`, so a single base settles it. The base is the smallest such `a`.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// // 3 * 2**2 + 1 = 13
+/// assert!(proth_test(&3.into(), 2));
+/// // 7 * 2**3 + 1 = 57 = 3 * 19
+/// assert!(!proth_test(&7.into(), 3));
+/// ```
+///
+/// # Panics
+/// - `k` isn't odd and positive
+/// - `k >= 2**n`
+pub fn proth_test(k: &BigInt, n: u32) -> bool {
+    check_input!(k.is_positive() && k.is_odd(), "k must be odd and positive");
+    check_input!(k.significant_bits() <= n, "k must be below 2**n");
+
+    let big_n: BigInt = BigInt::from(k << n) + 1;
+    // There are no non-residues modulo a square.
+    if big_n.is_perfect_square() {
+        return false;
+    }
+
+    let mut a = make_two();
+    let a = loop {
+        match a.jacobi(&big_n) {
+            -1 => break a,
+            // a shares a factor with N, and a < N as the non-residues start well below N.
+            0 => return false,
+            _ => a += 1,
+        }
+    };
+
+    let exponent = BigInt::from(&big_n >> 1);
+    let power = BigInt::from(
+        a.pow_mod_ref(&exponent, &big_n)
+            .expect("exponent is non-negative"),
+    );
+    power == BigInt::from(&big_n - 1)
+}
+
+/// The Lucas-Lehmer test: whether the Mersenne number `2**p - 1` is prime, for a prime `p`.
+///
+/// With `s_0 = 4` and `s_(i+1) = s_i**2 - 2`, the number `M = 2**p - 1` is prime exactly when
+/// `s_(p-2) = 0 mod M`. The answer is definite.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// assert!(lucas_lehmer_test(13));
+/// // 2**11 - 1 = 2047 = 23 * 89
+/// assert!(!lucas_lehmer_test(11));
+/// ```
+///
+/// # Panics
+/// - `p` isn't prime
+pub fn lucas_lehmer_test(p: u32) -> bool {
+    check_input!(is_prime_u64(p.into()), "p must be prime");
+    // The recurrence needs an odd p, and 2**2 - 1 = 3 is prime.
+    if p == 2 {
+        return true;
+    }
+
+    let mersenne = (BigInt::from(1) << p) - 1u32;
+    let mut s = BigInt::from(4);
+    for _ in 0..p - 2 {
+        s.square_mut();
+        s -= 2;
+        s.modulo_mut(&mersenne);
+    }
+    s == 0
+}
+
+/// Pépin's test: whether the Fermat number `F_m = 2**(2**m) + 1` is prime.
+///
+/// For `m >= 1`, `F_m` is prime exactly when `3**((F_m - 1) / 2) = -1 mod F_m`. The answer is
+/// definite, but the exponentiation takes `2**m` squarings, so large `m` are out of reach.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// assert!(pepin_test(4));
+/// // 2**32 + 1 = 641 * 6700417
+/// assert!(!pepin_test(5));
+/// ```
+///
+/// # Panics
+/// - `m > 31`
+pub fn pepin_test(m: u32) -> bool {
+    check_input!(m <= 31, "2**m must fit in a u32");
+    // 3 divides F_0 = 3 itself.
+    if m == 0 {
+        return true;
+    }
+
+    let exponent = 1u32 << m;
+    let fermat = (BigInt::from(1) << exponent) + 1u32;
+    let power = BigInt::from(
+        BigInt::from(3)
+            .pow_mod_ref(&(BigInt::from(1) << (exponent - 1)), &fermat)
+            .expect("exponent is non-negative"),
+    );
+    power == BigInt::from(&fermat - 1u32)
+}
+
+/// Tests `2**exp - 1`, or `2**exp + 1` if `plus` is set, with the test made for that form.
+///
+/// - `2**exp - 1` goes to [lucas_lehmer_test] when `exp` is prime. If `exp = a b` it is divisible
+///   by `2**a - 1`, so composite.
+/// - `2**exp + 1` goes to [pepin_test] when `exp` is a power of two. If `exp` has an odd factor
+///   `b > 1`, it is divisible by `2**(exp / b) + 1`, so composite.
+///
+/// Every case is settled, so the result is never [Primality::ProbablyPrime].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// assert_eq!(test_power_of_two_plus_minus(13, false), Primality::Prime);
+/// assert_eq!(test_power_of_two_plus_minus(16, true), Primality::Prime);
+/// assert_eq!(test_power_of_two_plus_minus(12, true), Primality::Composite);
+/// ```
+pub fn test_power_of_two_plus_minus(exp: u32, plus: bool) -> Primality {
+    let prime = if plus {
+        // 2**0 + 1 = 2.
+        exp == 0 || (exp.is_power_of_two() && pepin_test(exp.trailing_zeros()))
+    } else {
+        is_prime_u64(exp.into()) && lucas_lehmer_test(exp)
+    };
+
+    if prime {
+        Primality::Prime
+    } else {
+        Primality::Composite
+    }
+}
+
+/// The parameters `(D, P, Q)` of the Lucas sequence for a strong Lucas probable prime test, by
+/// Selfridge's method A, as used in the Baillie-PSW test.
+///
+/// `D` is the first of `5, -7, 9, -11, 13, ...` with the Jacobi symbol `(D/n) = -1`, and then
+/// `P = 1` and `Q = (1 - D) / 4`. A perfect square has `(D/n) = 1` for every `D`, so for those the
+/// search would never end and `None` is returned instead; they are composite unless `n = 1`.
+///
+/// A `D` with `(D/n) = 0` shares a factor with `n` and is skipped like any other. Callers that want
+/// to catch composites this way should check `gcd(D, n)` for the smaller `D` themselves.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// assert_eq!(selfridge_parameters(&97.into()), Some((5, 1, -1)));
+/// assert_eq!(selfridge_parameters(&11.into()), Some((13, 1, -3)));
+/// assert_eq!(selfridge_parameters(&49.into()), None);
+/// ```
+///
+/// # Panics
+/// - `n` isn't odd and positive
+pub fn selfridge_parameters(n: &BigInt) -> Option<(i64, i64, i64)> {
+    check_input!(n.is_positive() && n.is_odd(), "n must be odd and positive");
+    if n.is_perfect_square() {
+        return None;
+    }
+
+    let mut d: i64 = 5;
+    while BigInt::from(d).jacobi(n) != -1 {
+        d = if d > 0 { -d - 2 } else { -d + 2 };
+    }
+    Some((d, 1, (1 - d) / 4))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Primality {
+    Composite,
+    ProbablyPrime,
+    Prime,
+}
+
+/// A source of primality verdicts, for functions like [crate::factorization::factorize_with_oracle]
+/// that need to know whether a number is prime but don't care how it is found out. Implement it to
+/// plug in another test, for example a faster or hardware accelerated one.
+///
+/// Closures `Fn(&BigInt) -> Primality` are oracles too.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{primality::*, BigInt};
+/// let by_trial_division = |n: &BigInt| {
+///     if quick_composite_check(n) == Some(false) {
+///         Primality::Prime
+///     } else {
+///         Primality::Composite
+///     }
+/// };
+/// assert_eq!(by_trial_division.is_prime(&97.into()), Primality::Prime);
+/// ```
+pub trait PrimalityOracle {
+    /// Whether `n` is prime. [Primality::ProbablyPrime] and [Primality::Prime] are both taken to
+    /// mean prime, so a wrong answer can make callers panic or return wrong results.
+    fn is_prime(&self, n: &BigInt) -> Primality;
+}
+
+impl<F: Fn(&BigInt) -> Primality> PrimalityOracle for F {
+    fn is_prime(&self, n: &BigInt) -> Primality {
+        self(n)
+    }
+}
+
+/// The default [PrimalityOracle], running 25 rounds of [miller_rabin_rounds].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MillerRabinOracle;
+
+impl PrimalityOracle for MillerRabinOracle {
+    fn is_prime(&self, n: &BigInt) -> Primality {
+        if miller_rabin_rounds(n, NonZeroU32::new(25).unwrap()) {
+            Primality::ProbablyPrime
+        } else {
+            Primality::Composite
+        }
+    }
+}
+
+/// The outcome of [test_with_report], with the verdict and every test that led up to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrimalityReport {
+    pub primality: Primality,
+    /// The name of each test that ran, in order, and whether `n` passed it.
+    pub tests: Vec<(String, bool)>,
+}
+
+/// The Miller-Rabin bases that together make the test deterministic below
+/// [DETERMINISTIC_MILLER_RABIN_BOUND].
+const DETERMINISTIC_MILLER_RABIN_BASES: &[u32] = &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+/// No composite below this passes Miller-Rabin for all of [DETERMINISTIC_MILLER_RABIN_BASES].
+const DETERMINISTIC_MILLER_RABIN_BOUND: &str = "3317044064679887385961981";
+
+/// Classifies `n` like a combined probabilistic test would, but also records which tests ran and
+/// how they went, to show why `n` ended up where it did.
+///
+/// `n` first gets Fermat's test with base 2, then Miller-Rabin with the bases 2, 3, 5, ..., 41. It
+/// stops at the first failure. If everything passes and `n` is small enough that these bases are
+/// known to be conclusive, `n` is reported [Primality::Prime], otherwise
+/// [Primality::ProbablyPrime].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::primality::*;
+/// let report = test_with_report(&561.into());
+/// assert_eq!(report.primality, Primality::Composite);
+/// assert_eq!(report.tests[0], ("Fermat base 2".to_string(), true));
+/// assert_eq!(report.tests[1], ("Miller-Rabin base 2".to_string(), false));
+/// ```
+pub fn test_with_report(n: &BigInt) -> PrimalityReport {
+    let mut report = PrimalityReport {
+        primality: Primality::Composite,
+        tests: vec![],
+    };
+
+    if *n < 2 {
+        return report;
+    }
+
+    let passed = fermats_test(n, make_two());
+    report.tests.push(("Fermat base 2".to_string(), passed));
+    if !passed {
+        return report;
+    }
+
+    // Bases that are multiples of n tell us nothing, so only bases below n are used.
+    for base in DETERMINISTIC_MILLER_RABIN_BASES.iter().take_while(|a| **a < *n) {
+        let passed = miller_rabin_test(n, BigInt::from(*base));
+        report
+            .tests
+            .push((format!("Miller-Rabin base {}", base), passed));
+        if !passed {
+            return report;
+        }
+    }
+
+    let bound: BigInt = DETERMINISTIC_MILLER_RABIN_BOUND
+        .parse()
+        .expect("the bound should be a valid integer");
+    report.primality = if *n < bound {
+        Primality::Prime
+    } else {
+        Primality::ProbablyPrime
+    };
+    report
+}
+
+/// The primes up to this bound are divided out of `n - 1` when [prove_or_probable] looks for a
+/// Pocklington proof.
+const POCKLINGTON_TRIAL_BOUND: usize = 1 << 16;
+
+/// Tests `n` like [test_with_report], but tries to turn a [Primality::ProbablyPrime] into a proof.
+///
+/// Trial division and deterministic Miller-Rabin settle everything below
+/// [DETERMINISTIC_MILLER_RABIN_BOUND]. Above it, `n - 1` is trial divided by the primes up to
+/// `2**16`, and the cofactor left over is proven prime the same way, recursively. If the factored
+/// part is large enough, [pocklington_test] gives a proof. Only if that fails does the answer stay
+/// [Primality::ProbablyPrime].
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{primality::*, BigInt};
+/// assert_eq!(prove_or_probable(&97.into()), Primality::Prime);
+/// assert_eq!(prove_or_probable(&561.into()), Primality::Composite);
+/// // 165 * 2**100 + 1 is far above the Miller-Rabin bound, but n - 1 factors easily.
+/// let n = BigInt::from(165) * (BigInt::from(1) << 100) + 1;
+/// assert_eq!(prove_or_probable(&n), Primality::Prime);
+/// ```
+pub fn prove_or_probable(n: &BigInt) -> Primality {
+    match quick_composite_check(n) {
+        Some(true) => return Primality::Composite,
+        Some(false) => return Primality::Prime,
+        None => {}
+    }
+
+    let primality = test_with_report(n).primality;
+    if primality != Primality::ProbablyPrime {
+        return primality;
+    }
+
+    // Write n - 1 = F R, with F made up of small primes.
+    let mut rest = BigInt::from(n - 1);
+    let mut factored_part = vec![];
+    for p in esieve::sieve(POCKLINGTON_TRIAL_BOUND).expect("the bound is at least 2") {
+        let p = BigInt::from(p);
+        let exponent = rest.remove_factor_mut(&p);
+        if exponent > 0 {
+            factored_part.push((p, exponent));
+        }
+    }
+    // If R is a prime we can prove, all of n - 1 is factored.
+    if rest > 1 && prove_or_probable(&rest) == Primality::Prime {
+        factored_part.push((rest, 1));
+    }
+
+    if pocklington_test(n, &factored_part) {
+        Primality::Prime
+    } else {
+        Primality::ProbablyPrime
+    }
+}
+
+/// Tests each of `numbers` like [test_with_report] on rayon's thread pool, and returns the verdicts
+/// in the same order as `numbers`.
+///
+/// `progress` is called after each test with the number of tests completed so far. The calls come
+/// from the worker threads but are made one at a time and in increasing order, so the last one is
+/// `numbers.len()`. A slow `progress` holds up the other threads while it runs.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{primality::*, BigInt};
+/// let numbers: Vec<BigInt> = [97, 100, 561].map(BigInt::from).into();
+/// let verdicts = test_many_parallel(&numbers, |_| {});
+/// assert_eq!(verdicts, [Primality::Prime, Primality::Composite, Primality::Composite]);
+/// ```
+#[cfg(feature = "parallel")]
+pub fn test_many_parallel(numbers: &[BigInt], progress: impl Fn(usize) + Sync) -> Vec<Primality> {
+    use rayon::prelude::*;
+    use std::sync::Mutex;
+
+    let completed = Mutex::new(0);
+    numbers
+        .par_iter()
+        .map(|n| {
+            let primality = test_with_report(n).primality;
+            // Counting and reporting under the one lock keeps the reports in order.
+            let mut completed = completed.lock().expect("progress panicked");
+            *completed += 1;
+            progress(*completed);
+            primality
+        })
+        .collect()
+}
+
+/// Runs [miller_rabin_test] for each of `bases` on rayon's thread pool, passing only if `n` passes
+/// for all of them. Once a witness turns up, the bases not yet started are skipped.
+///
+/// # Example
+///
+/// ```
+/// use fermats_kitchen::{primality::*, BigInt};
+/// let bases = [2, 3, 5, 7].map(BigInt::from);
+/// assert!(miller_rabin_parallel(&1_000_000_007.into(), &bases));
+/// // 25326001 fools the bases 2, 3 and 5, but not 7.
+/// assert!(!miller_rabin_parallel(&25_326_001.into(), &bases));
+/// ```
+///
+/// # Panics
+/// The same as [miller_rabin_test], for any base that is reached.
+#[cfg(feature = "parallel")]
+pub fn miller_rabin_parallel(n: &BigInt, bases: &[BigInt]) -> bool {
+    use rayon::prelude::*;
+
+    // Without this, 1 would pass for an empty list of bases.
+    if let Some(prime) = handle_small_n(n) {
+        return prime;
+    }
+    bases.par_iter().all(|a| miller_rabin_test(n, a.clone()))
+}
+
+/// The 43 Carmichael numbers below `10**6`, in increasing order. Each one is composite yet passes
+/// [fermats_test] for every base, which makes them handy for checking primality tests.
+pub const SMALL_CARMICHAEL_NUMBERS: &[u32] = &[
+    561, 1105, 1729, 2465, 2821, 6601, 8911, 10585, 15841, 29341, 41041, 46657, 52633, 62745, 63973,
+    75361, 101101, 115921, 126217, 162401, 172081, 188461, 252601, 278545, 294409, 314821, 334153,
+    340561, 399001, 410041, 449065, 488881, 512461, 530881, 552721, 656601, 658801, 670033, 748657,
+    825265, 838201, 852841, 997633,
+];
+
+pub const FIRST_100_PRIMES: &[u32] = &[
     2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
     101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
     197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
@@ -0,0 +1,69 @@
+//! Montgomery arithmetic modulo an odd `u64`, with `R = 2**64`.
+//!
+//! Values in Montgomery form are stored as `x R mod n`. Multiplying two of them only needs a
+//! multiplication and a reduction by shifts, avoiding the slow `u128` division that a plain
+//! `a * b % n` costs.
+
+pub(super) struct Montgomery {
+    modulus: u64,
+    /// `modulus**-1 mod 2**64`
+    inverse: u64,
+}
+
+impl Montgomery {
+    /// # Panics
+    /// - `modulus` is even.
+    pub(super) fn new(modulus: u64) -> Self {
+        assert!(modulus % 2 == 1, "Montgomery form needs an odd modulus");
+
+        // Every odd n is its own inverse mod 8, each Newton step then doubles the correct bits.
+        let mut inverse = modulus;
+        for _ in 0..5 {
+            inverse = inverse.wrapping_mul(2u64.wrapping_sub(modulus.wrapping_mul(inverse)));
+        }
+
+        Self { modulus, inverse }
+    }
+
+    /// `t R**-1 mod n`, for `t < n R`.
+    fn reduce(&self, t: u128) -> u64 {
+        // m n has the same low 64 bits as t, so their difference is divisible by R. Subtracting
+        // the high halves gives (t - m n) / R without ever overflowing.
+        let m = (t as u64).wrapping_mul(self.inverse);
+        let mn = m as u128 * self.modulus as u128;
+        let (difference, borrowed) = ((t >> 64) as u64).overflowing_sub((mn >> 64) as u64);
+        if borrowed {
+            difference.wrapping_add(self.modulus)
+        } else {
+            difference
+        }
+    }
+
+    pub(super) fn to_montgomery(&self, x: u64) -> u64 {
+        (((x as u128) << 64) % self.modulus as u128) as u64
+    }
+
+    pub(super) fn to_normal(&self, x: u64) -> u64 {
+        self.reduce(x as u128)
+    }
+
+    /// Multiplies two values in Montgomery form.
+    pub(super) fn mont_mul(&self, a: u64, b: u64) -> u64 {
+        self.reduce(a as u128 * b as u128)
+    }
+
+    /// Raises a value in Montgomery form to `exponent`, by square and multiply.
+    pub(super) fn mont_pow(&self, mut base: u64, mut exponent: u64) -> u64 {
+        let mut result = self.to_montgomery(1);
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = self.mont_mul(result, base);
+            }
+            base = self.mont_mul(base, base);
+            exponent >>= 1;
+        }
+
+        result
+    }
+}
@@ -1,5 +1,3 @@
-use rug::integer::SmallInteger;
-
 use super::*;
 
 #[test]
@@ -7,7 +5,7 @@ fn test_fermats_test() {
     let a = BigInt::from(2);
 
     // Special case test for n == 1.
-    assert!(!fermats_test(&SmallInteger::from(1), a.clone()));
+    assert!(!fermats_test(&BigInt::from(1), a.clone()));
 
     for p in FIRST_100_PRIMES {
         let n = BigInt::from(*p);
@@ -22,8 +20,8 @@ fn test_fermats_test() {
 
     // These should pass. Test may be somewhat meaningless but if they don't, something is surely
     // amiss.
-    for carmichael in [561, 41041, 825265] {
-        let n = BigInt::from(carmichael);
+    for carmichael in SMALL_CARMICHAEL_NUMBERS {
+        let n = BigInt::from(*carmichael);
         assert!(fermats_test(&n, a.clone()));
     }
 }
@@ -57,9 +55,9 @@ fn test_miller_rabin_test() {
     let a = BigInt::from(2);
 
     // Special case test for n == 1.
-    assert!(!miller_rabin_test(&SmallInteger::from(1), a.clone()));
+    assert!(!miller_rabin_test(&BigInt::from(1), a.clone()));
     // Special case test for n == 2.
-    assert!(miller_rabin_test(&SmallInteger::from(2), a.clone()));
+    assert!(miller_rabin_test(&BigInt::from(2), a.clone()));
 
     for p in FIRST_100_PRIMES {
         let n = BigInt::from(*p);
@@ -81,6 +79,69 @@ fn test_miller_rabin_test() {
     // TODO: Test some pseudoprimes
 }
 
+#[test]
+fn test_miller_rabin_test_small_factors() {
+    // Base 1 passes the bare test for any n, so these are down to the trial division.
+    assert!(!miller_rabin_test(&BigInt::from(9999), BigInt::from(1)));
+    assert!(!miller_rabin_test(&9999u64, 1));
+    // 3 is a strong liar for 121 = 11**2.
+    assert!(!miller_rabin_test(&BigInt::from(121), BigInt::from(3)));
+    assert_eq!(pseudoprime_class(&BigInt::from(121), &BigInt::from(3)), PseudoprimeClass::Strong);
+
+    // The small primes themselves still pass.
+    assert!(miller_rabin_test(&BigInt::from(97), BigInt::from(2)));
+    assert!(miller_rabin_test(&97u64, 2));
+    for p in FIRST_100_PRIMES {
+        assert!(miller_rabin_test(&BigInt::from(*p), BigInt::from(1)));
+    }
+    // Factors past 97 are left to the bare test, which base 1 can't fail.
+    assert!(miller_rabin_test(&BigInt::from(101 * 103), BigInt::from(1)));
+}
+
+#[test]
+fn test_one_and_two() {
+    let rounds = NonZeroU32::new(10).unwrap();
+    let bases = [2, 3, 5].map(BigInt::from);
+
+    for (n, prime) in [(1u32, false), (2, true)] {
+        let big = BigInt::from(n);
+        for a in [1, 2, 3, 7] {
+            assert_eq!(fermats_test(&big, BigInt::from(a)), prime, "n = {}, a = {}", n, a);
+            assert_eq!(fermats_test_checked(&big, BigInt::from(a)), Ok(prime));
+            assert_eq!(miller_rabin_test(&big, BigInt::from(a)), prime, "n = {}, a = {}", n, a);
+            assert_eq!(miller_rabin_test_checked(&big, BigInt::from(a)), Ok(prime));
+            assert_eq!(miller_rabin_test(&u64::from(n), a), prime, "n = {}, a = {}", n, a);
+            assert_eq!(solovay_strassen_test(&big, BigInt::from(a)), prime, "n = {}, a = {}", n, a);
+        }
+        assert_eq!(fermat_test_bases(&big, &bases), prime);
+        assert_eq!(fermat_test_bases(&big, &[]), prime);
+        assert_eq!(miller_rabin_rounds(&big, rounds), prime);
+        assert_eq!(is_prime_u64(n.into()), prime);
+        assert_eq!(is_prime_bytes(&[n as u8], Order::Msf), prime);
+        assert_eq!(quick_composite_check(&big), Some(!prime));
+        assert_eq!(pocklington_test(&big, &[]), prime);
+        assert_eq!(pseudoprime_class(&big, &BigInt::from(2)), PseudoprimeClass::NotComposite);
+
+        let primality = if prime {
+            Primality::Prime
+        } else {
+            Primality::Composite
+        };
+        assert_eq!(test_with_report(&big).primality, primality);
+        assert_eq!(prove_or_probable(&big), primality);
+        assert_eq!(is_prime_timeout(&big, Duration::ZERO), Some(primality));
+    }
+
+    assert_eq!(
+        miller_rabin_confidence(&BigInt::from(2), rounds),
+        MrOutcome::ProbablePrime {
+            rounds_passed: 0,
+            error_bound: 0.0
+        }
+    );
+    assert_eq!(miller_rabin_evidence(&BigInt::from(2), BigInt::from(3)), MrEvidence::ProbablePrime);
+}
+
 #[test]
 #[should_panic]
 fn test_miller_rabin_test_zero_base() {
@@ -104,3 +165,1018 @@ fn test_miller_rabin_test_negative_n() {
     let a = BigInt::from(2);
     miller_rabin_test(&n, a);
 }
+
+// The input checks are only left out of release builds without the `strict-validation` feature,
+// so these also run under `cargo test --release --features strict-validation`.
+#[test]
+#[cfg(any(debug_assertions, feature = "strict-validation"))]
+#[should_panic(expected = "a must not be zero")]
+fn test_solovay_strassen_test_zero_base() {
+    solovay_strassen_test(&BigInt::from(7), BigInt::from(0));
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "strict-validation"))]
+#[should_panic(expected = "n must be positive")]
+fn test_solovay_strassen_test_negative_n() {
+    solovay_strassen_test(&BigInt::from(-7), BigInt::from(2));
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "strict-validation"))]
+#[should_panic(expected = "a must not be zero")]
+fn test_pseudoprime_class_zero_base() {
+    pseudoprime_class(&BigInt::from(561), &BigInt::from(0));
+}
+
+#[test]
+fn test_is_carmichael() {
+    for carmichael in SMALL_CARMICHAEL_NUMBERS {
+        assert!(is_carmichael(&BigInt::from(*carmichael)));
+    }
+
+    // Primes, even numbers and composites that aren't square-free or fail Korselt's criterion.
+    for n in [0, 1, 2, 3, 4, 15, 91, 341, 1387, 3 * 3 * 5 * 7] {
+        assert!(!is_carmichael(&BigInt::from(n)));
+    }
+
+    for p in FIRST_100_PRIMES {
+        assert!(!is_carmichael(&BigInt::from(*p)));
+    }
+}
+
+#[test]
+fn test_test_with_report() {
+    let report = test_with_report(&BigInt::from(561));
+    assert_eq!(report.primality, Primality::Composite);
+    assert_eq!(
+        report.tests,
+        vec![
+            ("Fermat base 2".to_string(), true),
+            ("Miller-Rabin base 2".to_string(), false)
+        ]
+    );
+
+    let report = test_with_report(&BigInt::from(15));
+    assert_eq!(report.primality, Primality::Composite);
+    assert_eq!(report.tests, vec![("Fermat base 2".to_string(), false)]);
+
+    let report = test_with_report(&BigInt::from(1));
+    assert_eq!(report.primality, Primality::Composite);
+    assert!(report.tests.is_empty());
+
+    for p in FIRST_100_PRIMES {
+        let report = test_with_report(&BigInt::from(*p));
+        assert_eq!(report.primality, Primality::Prime);
+        assert!(report.tests.iter().all(|(_, passed)| *passed));
+    }
+
+    // 2**127 - 1 is prime but too large for the bases to be conclusive.
+    let mersenne = (BigInt::from(1) << 127) - 1;
+    let report = test_with_report(&mersenne);
+    assert_eq!(report.primality, Primality::ProbablyPrime);
+    assert_eq!(report.tests.len(), 14);
+}
+
+#[test]
+fn test_miller_rabin_test_u64() {
+    for p in FIRST_100_PRIMES {
+        assert!(miller_rabin_test(&(*p as u64), 2));
+    }
+
+    for c in [2 * 3, 3 * 7, 2 * 11, 11 * 18, 53 * 59] {
+        assert!(!miller_rabin_test(&c, 2u64));
+    }
+
+    for carmichael in [561, 41041, 825265] {
+        assert!(!miller_rabin_test(&carmichael, 2u64));
+    }
+
+    // Products near the top of the range must not overflow. 2**61 - 1 and 2**64 - 59 are prime.
+    assert!(miller_rabin_test(&((1u64 << 61) - 1), 3));
+    assert!(miller_rabin_test(&(u64::MAX - 58), 3));
+    assert!(!miller_rabin_test(&u64::MAX, 3));
+}
+
+#[test]
+fn test_miller_rabin_test_u64_matches_bigint() {
+    for n in 3u64..2000 {
+        for a in [2u64, 3, 5] {
+            assert_eq!(
+                miller_rabin_test(&n, a),
+                miller_rabin_test(&BigInt::from(n), BigInt::from(a)),
+                "n = {}, a = {}",
+                n,
+                a
+            );
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_miller_rabin_test_u64_zero_n() {
+    miller_rabin_test(&0u64, 2);
+}
+
+#[test]
+fn test_quick_composite_check() {
+    for n in [-7i64, 0, 1, 4, 1_000_000, 541 * 541, 3 * 1_000_000_007] {
+        assert_eq!(quick_composite_check(&BigInt::from(n)), Some(true), "n = {}", n);
+    }
+    for p in FIRST_100_PRIMES {
+        assert_eq!(quick_composite_check(&BigInt::from(*p)), Some(false), "p = {}", p);
+    }
+    for n in [547 * 557, 1_000_000_007, 2_305_843_009_213_693_951u64] {
+        assert_eq!(quick_composite_check(&BigInt::from(n)), None, "n = {}", n);
+    }
+
+    // Below 541**2 every answer is definite and right.
+    for n in 2..541 * 541u64 {
+        assert_eq!(
+            quick_composite_check(&BigInt::from(n)),
+            Some(!is_prime_u64(n)),
+            "n = {}",
+            n
+        );
+    }
+}
+
+#[test]
+fn test_miller_rabin_oracle() {
+    for p in FIRST_100_PRIMES {
+        assert_eq!(MillerRabinOracle.is_prime(&BigInt::from(*p)), Primality::ProbablyPrime);
+    }
+    for n in [-7, 0, 1, 4, 561, 1_000_000] {
+        assert_eq!(MillerRabinOracle.is_prime(&BigInt::from(n)), Primality::Composite);
+    }
+    let mersenne = (BigInt::from(1) << 127) - 1;
+    assert_eq!(MillerRabinOracle.is_prime(&mersenne), Primality::ProbablyPrime);
+}
+
+#[test]
+fn test_lucas_lehmer_test() {
+    let mersenne_exponents: Vec<u32> = (2..128)
+        .filter(|p| is_prime_u64(*p as u64) && lucas_lehmer_test(*p))
+        .collect();
+    assert_eq!(mersenne_exponents, [2, 3, 5, 7, 13, 17, 19, 31, 61, 89, 107, 127]);
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "strict-validation"))]
+#[should_panic(expected = "p must be prime")]
+fn test_lucas_lehmer_test_composite_p() {
+    lucas_lehmer_test(15);
+}
+
+#[test]
+fn test_pepin_test() {
+    for m in 0..5 {
+        assert!(pepin_test(m), "m = {}", m);
+    }
+    for m in 5..12 {
+        assert!(!pepin_test(m), "m = {}", m);
+    }
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "strict-validation"))]
+#[should_panic(expected = "2**m must fit in a u32")]
+fn test_pepin_test_m_too_large() {
+    pepin_test(32);
+}
+
+#[test]
+fn test_test_power_of_two_plus_minus() {
+    assert_eq!(test_power_of_two_plus_minus(13, false), Primality::Prime);
+    assert_eq!(test_power_of_two_plus_minus(16, true), Primality::Prime);
+    assert_eq!(test_power_of_two_plus_minus(61, false), Primality::Prime);
+    assert_eq!(test_power_of_two_plus_minus(67, false), Primality::Composite);
+    assert_eq!(test_power_of_two_plus_minus(64, true), Primality::Composite);
+
+    for exp in 0..64 {
+        let power = 1u64 << exp;
+        for (plus, n) in [(true, power + 1), (false, power - 1)] {
+            let expected = if is_prime_u64(n) {
+                Primality::Prime
+            } else {
+                Primality::Composite
+            };
+            assert_eq!(test_power_of_two_plus_minus(exp, plus), expected, "n = {}", n);
+        }
+    }
+}
+
+#[test]
+fn test_selfridge_parameters() {
+    let expected = [
+        (3u64, 5i64),
+        (5, -7),
+        (7, 5),
+        (11, 13),
+        (19, -7),
+        (101, -7),
+        (1_000_000_007, 5),
+        (2_305_843_009_213_693_951, 17),
+        (561, 13),
+        (45, -7),
+    ];
+    for (n, d) in expected {
+        assert_eq!(
+            selfridge_parameters(&BigInt::from(n)),
+            Some((d, 1, (1 - d) / 4)),
+            "n = {}",
+            n
+        );
+    }
+
+    // D is the first of 5, -7, 9, ... with (D/n) = -1, and Q is an integer.
+    for n in (3..2000u32).step_by(2) {
+        let n = BigInt::from(n);
+        let Some((d, p, q)) = selfridge_parameters(&n) else {
+            assert!(n.is_perfect_square(), "n = {}", n);
+            continue;
+        };
+        assert_eq!(BigInt::from(d).jacobi(&n), -1);
+        assert_eq!((p, 4 * q), (1, 1 - d));
+        let mut earlier = 5i64;
+        while earlier != d {
+            assert_ne!(BigInt::from(earlier).jacobi(&n), -1, "n = {}", n);
+            earlier = if earlier > 0 { -earlier - 2 } else { -earlier + 2 };
+        }
+    }
+
+    for square in [1, 9, 25, 49, 1_000_000_007u64 * 1_000_000_007] {
+        assert_eq!(selfridge_parameters(&BigInt::from(square)), None, "n = {}", square);
+    }
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "strict-validation"))]
+#[should_panic(expected = "n must be odd and positive")]
+fn test_selfridge_parameters_even_n() {
+    selfridge_parameters(&BigInt::from(10));
+}
+
+#[test]
+fn test_is_prime_u64() {
+    for p in FIRST_100_PRIMES {
+        assert!(is_prime_u64(*p as u64));
+    }
+
+    for n in [0, 1, 4, 9, 561, 41041, 825265] {
+        assert!(!is_prime_u64(n));
+    }
+
+    // Strong pseudoprimes to base 2, the last one to every prime base up to 23.
+    for pseudoprime in [2047, 3277, 4033, 3215031751, 3825123056546413051] {
+        assert!(!is_prime_u64(pseudoprime));
+    }
+
+    // 2**61 - 1, 2**64 - 59, and the largest u64 which is composite.
+    assert!(is_prime_u64((1 << 61) - 1));
+    assert!(is_prime_u64(u64::MAX - 58));
+    assert!(!is_prime_u64(u64::MAX));
+    // Product of two primes just above 2**31.
+    assert!(!is_prime_u64(2147483659 * 2147483693));
+
+    for n in 0u64..10_000 {
+        assert_eq!(
+            is_prime_u64(n),
+            BigInt::from(n).is_probably_prime(30) != rug::integer::IsPrime::No,
+            "n = {}",
+            n
+        );
+    }
+}
+
+#[test]
+fn test_mod_pow_u64() {
+    let mut rand = rug::rand::RandState::new();
+    rand.seed(&BigInt::from(109));
+    let mut random_u64 = || BigInt::from(BigInt::random_bits(64, &mut rand)).to_u64().unwrap();
+
+    for _ in 0..2000 {
+        let (base, exponent, modulus) = (random_u64(), random_u64(), random_u64().max(1));
+        let expected = BigInt::from(base)
+            .pow_mod(&BigInt::from(exponent), &BigInt::from(modulus))
+            .unwrap();
+        assert_eq!(
+            mod_pow_u64(base, exponent, modulus),
+            expected,
+            "{}**{} mod {}",
+            base,
+            exponent,
+            modulus
+        );
+    }
+
+    // Small and edge-case moduli, both odd and even.
+    for modulus in [1, 2, 3, 4, 5, 8, 9, u64::MAX, u64::MAX - 1] {
+        for base in [0, 1, 2, 7, u64::MAX] {
+            for exponent in [0, 1, 2, 63, 64, u64::MAX] {
+                let expected = BigInt::from(base)
+                    .pow_mod(&BigInt::from(exponent), &BigInt::from(modulus))
+                    .unwrap();
+                assert_eq!(mod_pow_u64(base, exponent, modulus), expected);
+            }
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_mod_pow_u64_zero_modulus() {
+    mod_pow_u64(2, 3, 0);
+}
+
+#[test]
+fn test_montgomery_round_trip() {
+    for modulus in [3, 97, 1 << 61 | 1, u64::MAX] {
+        let montgomery = montgomery::Montgomery::new(modulus);
+        for x in [0, 1, 2, modulus / 2, modulus - 1] {
+            let x_mont = montgomery.to_montgomery(x);
+            assert_eq!(montgomery.to_normal(x_mont), x);
+
+            let square = montgomery.to_normal(montgomery.mont_mul(x_mont, x_mont));
+            assert_eq!(square as u128, x as u128 * x as u128 % modulus as u128);
+        }
+    }
+}
+
+#[test]
+fn test_fermat_liars() {
+    let liars: Vec<BigInt> = [4, 5, 6, 9, 10, 11].map(BigInt::from).into();
+    assert_eq!(fermat_liars(&BigInt::from(15)), liars);
+
+    // A Carmichael number lies for every base. Of those, the ones coprime to 561 are counted by
+    // phi(561) = 2 * 10 * 16 = 320, minus the bases 1 and 560 outside the range.
+    let n = BigInt::from(561);
+    let liars = fermat_liars(&n);
+    assert_eq!(liars.len(), 561 - 3);
+    let coprime = liars.iter().filter(|a| BigInt::from(a.gcd_ref(&n)) == 1).count();
+    assert_eq!(coprime, 320 - 2);
+
+    for p in FIRST_100_PRIMES {
+        assert!(fermat_liars(&BigInt::from(*p)).is_empty());
+    }
+    for n in [0, 1, 4] {
+        assert!(fermat_liars(&BigInt::from(n)).is_empty());
+    }
+}
+
+#[test]
+fn test_fermat_witnesses() {
+    let witnesses: Vec<BigInt> = [2, 3, 7, 8, 12, 13].map(BigInt::from).into();
+    assert_eq!(fermat_witnesses(&BigInt::from(15), 100), witnesses);
+    assert_eq!(fermat_witnesses(&BigInt::from(15), 7), witnesses[..3]);
+
+    // Witnesses and liars split the bases between them.
+    for n in [15, 91, 341, 1001] {
+        let n = BigInt::from(n);
+        let mut bases = fermat_witnesses(&n, usize::MAX);
+        bases.extend(fermat_liars(&n));
+        bases.sort();
+        let expected: Vec<BigInt> = (2..=n.to_u32().unwrap() - 2).map(BigInt::from).collect();
+        assert_eq!(bases, expected, "n = {}", n);
+    }
+
+    // A Carmichael number passes for every base, even the ones sharing a factor with it.
+    for n in SMALL_CARMICHAEL_NUMBERS.iter().take(5) {
+        assert!(fermat_witnesses(&BigInt::from(*n), usize::MAX).is_empty(), "n = {}", n);
+    }
+
+    for p in FIRST_100_PRIMES {
+        assert!(fermat_witnesses(&BigInt::from(*p), usize::MAX).is_empty());
+    }
+    assert!(fermat_witnesses(&BigInt::from(0), 100).is_empty());
+    assert!(fermat_witnesses(&BigInt::from(15), 1).is_empty());
+}
+
+#[test]
+fn test_miller_rabin_confidence() {
+    let rounds = NonZeroU32::new(10).unwrap();
+
+    for p in [1_000_000_007u64, 2_305_843_009_213_693_951] {
+        assert_eq!(
+            miller_rabin_confidence(&BigInt::from(p), rounds),
+            MrOutcome::ProbablePrime {
+                rounds_passed: 10,
+                error_bound: 1.0 / 1_048_576.0
+            }
+        );
+    }
+    // 7 only has the bases 2 to 5, so all of them are tried.
+    assert_eq!(
+        miller_rabin_confidence(&BigInt::from(7), rounds),
+        MrOutcome::ProbablePrime {
+            rounds_passed: 4,
+            error_bound: 0.0
+        }
+    );
+    assert_eq!(
+        miller_rabin_confidence(&BigInt::from(1_000_000), rounds),
+        MrOutcome::Composite {
+            failed_at_round: 0,
+            witness: BigInt::from(2)
+        }
+    );
+    // The bases differ from call to call, but the witness must really prove `n` composite.
+    for n in [9u64, 561, 2047, 3215031751, 1_000_003 * 1_000_033] {
+        let n = BigInt::from(n);
+        let MrOutcome::Composite {
+            failed_at_round,
+            witness,
+        } = miller_rabin_confidence(&n, rounds)
+        else {
+            panic!("{} should be composite", n);
+        };
+        assert!((1..=10).contains(&failed_at_round));
+        assert!(
+            !number_theory::are_coprime(&witness, &n) || !miller_rabin_test(&n, witness),
+            "n = {}",
+            n
+        );
+    }
+}
+
+#[test]
+fn test_miller_rabin_confidence_small_n() {
+    let rounds = NonZeroU32::new(10).unwrap();
+    for n in [-3, 0, 1] {
+        assert_eq!(
+            miller_rabin_confidence(&BigInt::from(n), rounds),
+            MrOutcome::Composite {
+                failed_at_round: 0,
+                witness: BigInt::from(2)
+            },
+            "n = {}",
+            n
+        );
+    }
+    for p in [2, 3] {
+        assert_eq!(
+            miller_rabin_confidence(&BigInt::from(p), rounds),
+            MrOutcome::ProbablePrime {
+                rounds_passed: 0,
+                error_bound: 0.0
+            }
+        );
+    }
+}
+
+#[test]
+fn test_miller_rabin_rounds() {
+    let rounds = NonZeroU32::new(10).unwrap();
+
+    for p in FIRST_100_PRIMES {
+        assert!(miller_rabin_rounds(&BigInt::from(*p), rounds));
+    }
+    for n in [-3i64, 0, 1, 4, 9, 561, 2047, 3215031751, 41041, 825265] {
+        assert!(!miller_rabin_rounds(&BigInt::from(n), rounds));
+    }
+
+    let mersenne = (BigInt::from(1) << 127) - 1;
+    assert!(miller_rabin_rounds(&mersenne, rounds));
+    assert!(!miller_rabin_rounds(&BigInt::from(&mersenne + 2), rounds));
+}
+
+#[test]
+fn test_random_bases() {
+    let n = BigInt::from(1_000_000_007);
+    let bases: Vec<BigInt> = RandomBases::new(&n).take(5000).collect();
+    assert_eq!(bases.len(), 5000);
+    assert!(bases.iter().all(|a| *a >= 2 && *a <= BigInt::from(&n - 2)));
+    let distinct: HashSet<&BigInt> = bases.iter().collect();
+    assert_eq!(distinct.len(), bases.len());
+
+    // Small ranges run out, having tried every base exactly once.
+    let mut bases: Vec<BigInt> = RandomBases::new(&BigInt::from(101)).collect();
+    bases.sort();
+    assert_eq!(bases, (2..=99).map(BigInt::from).collect::<Vec<_>>());
+    for n in [-3, 0, 1, 2, 3] {
+        assert_eq!(RandomBases::new(&BigInt::from(n)).next(), None);
+    }
+    assert_eq!(RandomBases::new(&BigInt::from(5)).count(), 2);
+
+    // Only a seed makes the bases repeat.
+    let seeded = |seed| RandomBases::with_seed(&n, seed).take(10).collect::<Vec<_>>();
+    assert_eq!(seeded(162), seeded(162));
+    assert_ne!(seeded(162), seeded(163));
+    let unseeded = || RandomBases::new(&n).take(10).collect::<Vec<_>>();
+    assert_ne!(unseeded(), unseeded());
+}
+
+#[test]
+fn test_is_prime_bytes() {
+    let mersenne: BigInt = (BigInt::from(1) << 127) - 1;
+    for order in [Order::Lsf, Order::Msf] {
+        let bytes = mersenne.to_digits::<u8>(order);
+        assert_eq!(BigInt::from_digits(&bytes, order), mersenne);
+        assert!(is_prime_bytes(&bytes, order));
+    }
+
+    // 2**127 + 1 is divisible by 3.
+    let bytes = BigInt::from(&mersenne + 2).to_digits::<u8>(Order::Msf);
+    assert!(!is_prime_bytes(&bytes, Order::Msf));
+
+    // The order matters: 0x0107 = 263 is prime but 0x0701 = 1793 = 11 * 163.
+    assert!(is_prime_bytes(&[0x01, 0x07], Order::Msf));
+    assert!(!is_prime_bytes(&[0x01, 0x07], Order::Lsf));
+    assert!(!is_prime_bytes(&[], Order::Lsf));
+}
+
+#[test]
+fn test_fermats_test_checked() {
+    assert_eq!(fermats_test_checked(&BigInt::from(11), BigInt::from(2)), Ok(true));
+    assert_eq!(fermats_test_checked(&BigInt::from(8), BigInt::from(2)), Ok(false));
+    assert_eq!(fermats_test_checked(&BigInt::from(1), BigInt::from(2)), Ok(false));
+
+    assert_eq!(
+        fermats_test_checked(&BigInt::from(3), BigInt::from(0)),
+        Err(Error::ZeroBase)
+    );
+    assert_eq!(
+        fermats_test_checked(&BigInt::from(0), BigInt::from(2)),
+        Err(Error::NonPositiveN)
+    );
+    assert_eq!(
+        fermats_test_checked(&BigInt::from(-3), BigInt::from(2)),
+        Err(Error::NonPositiveN)
+    );
+}
+
+#[test]
+fn test_miller_rabin_test_checked() {
+    assert_eq!(miller_rabin_test_checked(&BigInt::from(2), BigInt::from(2)), Ok(true));
+    assert_eq!(miller_rabin_test_checked(&BigInt::from(97), BigInt::from(2)), Ok(true));
+    assert_eq!(miller_rabin_test_checked(&BigInt::from(561), BigInt::from(2)), Ok(false));
+    assert_eq!(miller_rabin_test_checked(&97u64, 2), Ok(true));
+
+    assert_eq!(
+        miller_rabin_test_checked(&BigInt::from(3), BigInt::from(0)),
+        Err(Error::ZeroBase)
+    );
+    assert_eq!(
+        miller_rabin_test_checked(&BigInt::from(0), BigInt::from(2)),
+        Err(Error::NonPositiveN)
+    );
+    assert_eq!(
+        miller_rabin_test_checked(&BigInt::from(-3), BigInt::from(2)),
+        Err(Error::NonPositiveN)
+    );
+    assert_eq!(miller_rabin_test_checked(&3u64, 0), Err(Error::ZeroBase));
+    assert_eq!(miller_rabin_test_checked(&0u64, 2), Err(Error::NonPositiveN));
+}
+
+fn factored(factors: &[(u64, u32)]) -> Vec<(BigInt, u32)> {
+    factors.iter().map(|(q, e)| (BigInt::from(*q), *e)).collect()
+}
+
+#[test]
+fn test_pocklington_test() {
+    // Full factorizations of n - 1.
+    assert!(pocklington_test(&BigInt::from(3), &factored(&[(2, 1)])));
+    assert!(pocklington_test(
+        &BigInt::from(104_729),
+        &factored(&[(2, 3), (13, 1), (19, 1), (53, 1)])
+    ));
+    assert!(pocklington_test(
+        &BigInt::from(1_000_000_007u64),
+        &factored(&[(2, 1), (500_000_003, 1)])
+    ));
+
+    // Partial factorizations, just past sqrt(n).
+    assert!(pocklington_test(&BigInt::from(998_244_353), &factored(&[(2, 23)])));
+    assert!(pocklington_test(
+        &BigInt::from(2_147_483_647),
+        &factored(&[(2, 1), (3, 2), (7, 1), (11, 1), (151, 1)])
+    ));
+    let mersenne = BigInt::from(2_305_843_009_213_693_951u64);
+    let partial = factored(&[
+        (2, 1),
+        (3, 2),
+        (5, 2),
+        (7, 1),
+        (11, 1),
+        (13, 1),
+        (31, 1),
+        (41, 1),
+        (61, 1),
+    ]);
+    assert!(pocklington_test(&mersenne, &partial));
+}
+
+#[test]
+fn test_pocklington_test_unsatisfiable() {
+    // The factored part is below sqrt(n).
+    assert!(!pocklington_test(&BigInt::from(998_244_353), &factored(&[(2, 10)])));
+    assert!(!pocklington_test(&BigInt::from(104_729), &[]));
+    // The factored part doesn't divide n - 1.
+    assert!(!pocklington_test(&BigInt::from(104_729), &factored(&[(3, 20)])));
+    // Composites, even Carmichael numbers, fail with a full factorization of n - 1.
+    assert!(!pocklington_test(&BigInt::from(561), &factored(&[(2, 4), (5, 1), (7, 1)])));
+    assert!(!pocklington_test(&BigInt::from(91), &factored(&[(2, 1), (3, 2), (5, 1)])));
+    assert!(pocklington_test(&BigInt::from(2), &[]));
+    assert!(!pocklington_test(&BigInt::from(1), &[]));
+}
+
+#[test]
+fn test_proth_test() {
+    // 2**1 + 1, 3 * 2**2 + 1, 5 * 2**3 + 1 and 2**16 + 1.
+    for (k, n) in [(1, 1), (3, 2), (5, 3), (1, 16)] {
+        assert!(proth_test(&BigInt::from(k), n), "{} * 2**{} + 1", k, n);
+    }
+    // 57 = 3 * 19, 2**32 + 1 = 641 * 6700417 and 9 * 2**4 + 1 = 145 = 5 * 29.
+    for (k, n) in [(7, 3), (1, 32), (9, 4)] {
+        assert!(!proth_test(&BigInt::from(k), n), "{} * 2**{} + 1", k, n);
+    }
+
+    for n in 1..=10 {
+        for k in (1..1u32 << n).step_by(2) {
+            let proth: BigInt = BigInt::from(k) * (1u32 << n) + 1;
+            let expected = proth.is_probably_prime(30) != rug::integer::IsPrime::No;
+            assert_eq!(proth_test(&BigInt::from(k), n), expected, "{}", proth);
+        }
+    }
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "strict-validation"))]
+#[should_panic]
+fn test_proth_test_even_k() {
+    proth_test(&BigInt::from(4), 3);
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "strict-validation"))]
+#[should_panic]
+fn test_proth_test_k_too_large() {
+    proth_test(&BigInt::from(9), 3);
+}
+
+/// [miller_rabin_test] as it was before the witness loop squared in place, to check the verdicts
+/// haven't changed.
+fn miller_rabin_by_pow_mod(n: u64, a: u64) -> bool {
+    if n == 2 {
+        return true;
+    }
+    let mut q = n - 1;
+    let mut k = 0;
+    while q > 1 && q.is_multiple_of(2) {
+        k += 1;
+        q /= 2;
+    }
+    let mut a = mod_pow_u64(a, q, n);
+    if a == 1 {
+        return true;
+    }
+    for _ in 0..k {
+        if a == n - 1 {
+            return true;
+        }
+        a = mod_pow_u64(a, 2, n);
+    }
+    false
+}
+
+#[test]
+fn test_miller_rabin_squaring_regression() {
+    // Strong pseudoprimes to base 2 and to bases 2 and 3, Carmichael numbers and plain composites.
+    let composites = [2047u64, 3277, 4033, 1_373_653, 561, 41041, 825265, 15, 91, 1001, 65535];
+    // 2 is settled before the bare test is reached.
+    let primes = FIRST_100_PRIMES[1..].iter().map(|p| *p as u64);
+
+    // The squaring is in the bare test, miller_rabin_test fails small factors before reaching it.
+    for n in primes.chain(composites) {
+        for a in [2u64, 3, 5, 7, 10] {
+            let expected = miller_rabin_by_pow_mod(n, a);
+            assert_eq!(strong_probable_prime_test(&n, a), expected, "n = {}, a = {}", n, a);
+            assert_eq!(
+                strong_probable_prime_test(&BigInt::from(n), BigInt::from(a)),
+                expected,
+                "n = {}, a = {}",
+                n,
+                a
+            );
+        }
+    }
+
+    // 2047 = 23 * 89 has a small factor, 1373653 = 829 * 1657 doesn't.
+    assert!(strong_probable_prime_test(&2047u64, 2));
+    assert!(!miller_rabin_test(&2047u64, 2));
+    assert!(miller_rabin_test(&BigInt::from(1_373_653), BigInt::from(3)));
+    assert!(!miller_rabin_test(&BigInt::from(1_373_653), BigInt::from(5)));
+}
+
+#[test]
+fn test_miller_rabin_evidence() {
+    let evidence = |n: u64, a: u64| miller_rabin_evidence(&BigInt::from(n), BigInt::from(a));
+    let factor = |f: u64| MrEvidence::CompositeByFactor(BigInt::from(f));
+
+    assert_eq!(evidence(2, 3), MrEvidence::ProbablePrime);
+    assert_eq!(evidence(97, 5), MrEvidence::ProbablePrime);
+    // A strong pseudoprime to base 2.
+    assert_eq!(evidence(2047, 2), MrEvidence::ProbablePrime);
+    assert_eq!(evidence(9, 2), MrEvidence::CompositeByWitness(BigInt::from(2)));
+
+    // n shares a factor with the base.
+    assert_eq!(evidence(15, 3), factor(3));
+    assert_eq!(evidence(21, 7), factor(7));
+    assert_eq!(evidence(25, 10), factor(5));
+    assert_eq!(evidence(49, 7), factor(7));
+    assert_eq!(evidence(3 * 1_000_000_007, 6), factor(3));
+    assert_eq!(evidence(8, 3), factor(2));
+
+    // A nontrivial square root of 1 shows up while squaring.
+    assert_eq!(evidence(561, 2), factor(33));
+    assert_eq!(evidence(1105, 2), factor(65));
+    assert_eq!(evidence(41041, 2), factor(77));
+
+    // The verdicts agree with the bare Miller-Rabin test, which doesn't trial divide. Even n is
+    // left out, since a base of 1 mod n lets it pass that test.
+    for n in (3..2000u64).step_by(2) {
+        for a in [2u64, 3, 5] {
+            let passed = evidence(n, a) == MrEvidence::ProbablePrime;
+            assert_eq!(passed, strong_probable_prime_test(&n, a), "n = {}, a = {}", n, a);
+        }
+    }
+}
+
+#[test]
+fn test_miller_rabin_evidence_small_n() {
+    // Below 2 nothing is prime, and the base is all the evidence there is.
+    for n in [-3, 0, 1] {
+        assert_eq!(
+            miller_rabin_evidence(&BigInt::from(n), BigInt::from(2)),
+            MrEvidence::CompositeByWitness(BigInt::from(2)),
+            "n = {}",
+            n
+        );
+    }
+    assert_eq!(miller_rabin_evidence(&BigInt::from(2), BigInt::from(5)), MrEvidence::ProbablePrime);
+    assert_eq!(miller_rabin_evidence(&BigInt::from(3), BigInt::from(2)), MrEvidence::ProbablePrime);
+    assert_eq!(
+        miller_rabin_evidence(&BigInt::from(3), BigInt::from(3)),
+        MrEvidence::CompositeByWitness(BigInt::from(3))
+    );
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "strict-validation"))]
+#[should_panic]
+fn test_miller_rabin_evidence_zero_base() {
+    miller_rabin_evidence(&BigInt::from(7), BigInt::from(0));
+}
+
+#[test]
+fn test_fermats_test_base_minus_one() {
+    let seven = BigInt::from(7);
+    for a in [6, 13, -1, -8] {
+        assert_eq!(
+            fermats_test_checked(&seven, BigInt::from(a)),
+            Err(Error::BaseGivesNoInfo),
+            "a = {}",
+            a
+        );
+        assert!(fermats_test(&seven, BigInt::from(a)));
+    }
+    assert_eq!(fermats_test_checked(&seven, BigInt::from(5)), Ok(true));
+
+    // Composites pass too, which is why the checked test refuses.
+    assert_eq!(
+        fermats_test_checked(&BigInt::from(9), BigInt::from(8)),
+        Err(Error::BaseGivesNoInfo)
+    );
+    assert!(fermats_test(&BigInt::from(9), BigInt::from(8)));
+
+    // For even n, (-1)**n = 1 so the test still tells something.
+    assert_eq!(fermats_test_checked(&BigInt::from(8), BigInt::from(7)), Ok(false));
+}
+
+#[test]
+fn test_is_prime_timeout() {
+    let plenty = Duration::from_secs(60);
+    assert_eq!(is_prime_timeout(&BigInt::from(97), plenty), Some(Primality::ProbablyPrime));
+    assert_eq!(is_prime_timeout(&BigInt::from(561), plenty), Some(Primality::Composite));
+    let semiprime = BigInt::from(1_000_000_007u64) * BigInt::from(1_000_000_009u64);
+    assert_eq!(is_prime_timeout(&semiprime, plenty), Some(Primality::Composite));
+
+    // Settled without running any rounds, so even no budget at all is enough.
+    assert_eq!(is_prime_timeout(&BigInt::from(3), Duration::ZERO), Some(Primality::Prime));
+    assert_eq!(is_prime_timeout(&BigInt::from(1), Duration::ZERO), Some(Primality::Composite));
+    let even = BigInt::from(BigInt::u_pow_u(2, 4423)) + 2;
+    assert_eq!(is_prime_timeout(&even, Duration::ZERO), Some(Primality::Composite));
+
+    // Without a budget not even one round runs, however large n is.
+    let mersenne = BigInt::from(BigInt::u_pow_u(2, 4423)) - 1;
+    assert_eq!(is_prime_timeout(&mersenne, Duration::ZERO), None);
+}
+
+#[test]
+fn test_fermat_test_bases() {
+    let bases = |bases: &[i32]| bases.iter().map(|a| BigInt::from(*a)).collect::<Vec<_>>();
+
+    assert!(fermat_test_bases(&BigInt::from(561), &bases(&[2, 3])));
+    assert!(fermat_test_bases(&BigInt::from(561), &bases(&[2, 3, 5, 7, 11])));
+    assert!(fermat_test_bases(&BigInt::from(97), &bases(&[2, 3, 5])));
+    assert!(fermat_test_bases(&BigInt::from(97), &[]));
+
+    // 341 = 11 * 31 fools base 2 but not base 3.
+    assert!(fermat_test_bases(&BigInt::from(341), &bases(&[2])));
+    assert!(!fermat_test_bases(&BigInt::from(341), &bases(&[2, 3])));
+
+    // Stops at the failing base 2, so the base 0 is never used and doesn't panic.
+    assert!(!fermat_test_bases(&BigInt::from(15), &bases(&[2, 0])));
+}
+
+#[test]
+fn test_prime_filter_ext() {
+    let primes: Vec<BigInt> = (1..100).map(BigInt::from).primes().collect();
+    let expected: Vec<BigInt> = FIRST_100_PRIMES
+        .iter()
+        .take_while(|p| **p < 100)
+        .map(|p| BigInt::from(*p))
+        .collect();
+    assert_eq!(primes, expected);
+
+    let primes: Vec<BigInt> = (-10..2).map(BigInt::from).primes().collect();
+    assert!(primes.is_empty());
+
+    // Carmichael numbers and strong pseudoprimes to base 2 are filtered out too.
+    let primes: Vec<BigInt> = [561, 2047, 1_373_653, 97, 1_000_000_007]
+        .into_iter()
+        .map(BigInt::from)
+        .primes()
+        .collect();
+    assert_eq!(primes, [97, 1_000_000_007]);
+}
+
+#[test]
+fn test_solovay_strassen_test() {
+    for p in FIRST_100_PRIMES {
+        for a in [2, 3, 5, 7] {
+            if p % a != 0 {
+                assert!(solovay_strassen_test(&BigInt::from(*p), BigInt::from(a)));
+            }
+        }
+    }
+    assert!(!solovay_strassen_test(&BigInt::from(1), BigInt::from(2)));
+    assert!(!solovay_strassen_test(&BigInt::from(8), BigInt::from(3)));
+    assert!(!solovay_strassen_test(&BigInt::from(15), BigInt::from(3)));
+    assert!(!solovay_strassen_test(&BigInt::from(341), BigInt::from(2)));
+    // 561 is an Euler pseudoprime to base 2.
+    assert!(solovay_strassen_test(&BigInt::from(561), BigInt::from(2)));
+}
+
+#[test]
+fn test_pseudoprime_class() {
+    let class = |n: u32, a: u32| pseudoprime_class(&BigInt::from(n), &BigInt::from(a));
+
+    assert_eq!(class(2047, 2), PseudoprimeClass::Strong);
+    assert_eq!(class(3277, 2), PseudoprimeClass::Strong);
+    assert_eq!(class(121, 3), PseudoprimeClass::Strong);
+    assert_eq!(class(561, 2), PseudoprimeClass::Euler);
+    assert_eq!(class(1105, 2), PseudoprimeClass::Euler);
+    assert_eq!(class(341, 2), PseudoprimeClass::Fermat);
+    assert_eq!(class(91, 3), PseudoprimeClass::Fermat);
+    // Even composites stop at Fermat pseudoprimes.
+    assert_eq!(class(4, 5), PseudoprimeClass::Fermat);
+    assert_eq!(class(15, 2), PseudoprimeClass::NotPseudoprime);
+    assert_eq!(class(4, 2), PseudoprimeClass::NotPseudoprime);
+    assert_eq!(class(97, 2), PseudoprimeClass::NotComposite);
+    assert_eq!(class(1, 2), PseudoprimeClass::NotComposite);
+
+    // The classes nest: passing a stronger test means passing the weaker ones.
+    for n in (9..5000u32).step_by(2) {
+        for a in [2, 3, 5] {
+            let c = class(n, a);
+            if c >= PseudoprimeClass::Euler {
+                assert!(fermats_test(&BigInt::from(n), BigInt::from(a)), "n = {}", n);
+            }
+            if c == PseudoprimeClass::Strong {
+                assert!(
+                    solovay_strassen_test(&BigInt::from(n), BigInt::from(a)),
+                    "n = {}",
+                    n
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_small_carmichael_numbers() {
+    let rounds = NonZeroU32::new(25).unwrap();
+    for carmichael in SMALL_CARMICHAEL_NUMBERS {
+        let n = BigInt::from(*carmichael);
+        assert!(!miller_rabin_rounds(&n, rounds), "{}", n);
+        assert!(fermats_test(&n, BigInt::from(2)), "{}", n);
+    }
+
+    assert!(SMALL_CARMICHAEL_NUMBERS.windows(2).all(|pair| pair[0] < pair[1]));
+    // The list is complete below 10**6.
+    let carmichaels: Vec<u32> = (1..1_000_000)
+        .step_by(2)
+        .filter(|n| is_carmichael(&BigInt::from(*n)))
+        .collect();
+    assert_eq!(carmichaels, SMALL_CARMICHAEL_NUMBERS);
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn test_many_parallel_in_order() {
+    use std::sync::Mutex;
+
+    let numbers: Vec<BigInt> = (0..20_000u64)
+        .map(BigInt::from)
+        .chain(SMALL_CARMICHAEL_NUMBERS.iter().map(|n| BigInt::from(*n)))
+        .chain([(BigInt::from(1) << 127) - 1, (BigInt::from(1) << 128) + 1])
+        .collect();
+
+    let reports = Mutex::new(vec![]);
+    let verdicts =
+        test_many_parallel(&numbers, |completed| reports.lock().unwrap().push(completed));
+    let reports = reports.into_inner().unwrap();
+    assert!(reports.iter().copied().eq(1..=numbers.len()));
+
+    assert_eq!(verdicts.len(), numbers.len());
+    for (n, verdict) in numbers.iter().zip(&verdicts) {
+        assert_eq!(*verdict, test_with_report(n).primality, "n = {}", n);
+    }
+    let prime_count = verdicts[..20_000]
+        .iter()
+        .filter(|verdict| **verdict == Primality::Prime)
+        .count();
+    assert_eq!(prime_count, 2262);
+    assert_eq!(verdicts[verdicts.len() - 2], Primality::ProbablyPrime);
+    assert_eq!(verdicts[verdicts.len() - 1], Primality::Composite);
+}
+
+#[test]
+fn prove_or_probable_small_primes_are_proven() {
+    for p in FIRST_100_PRIMES {
+        assert_eq!(prove_or_probable(&BigInt::from(*p)), Primality::Prime, "p = {}", p);
+    }
+    for p in [1_000_003u64, 1_000_000_007, 2_305_843_009_213_693_951] {
+        assert_eq!(prove_or_probable(&BigInt::from(p)), Primality::Prime, "p = {}", p);
+    }
+}
+
+#[test]
+fn prove_or_probable_composites() {
+    for n in [0, 1, 4, 1_000_000].into_iter().chain(SMALL_CARMICHAEL_NUMBERS.iter().copied()) {
+        assert_eq!(prove_or_probable(&BigInt::from(n)), Primality::Composite, "n = {}", n);
+    }
+    let n = (BigInt::from(1) << 128) + 1;
+    assert_eq!(prove_or_probable(&n), Primality::Composite);
+}
+
+#[test]
+fn prove_or_probable_large_primes() {
+    // 165 * 2**100 + 1: n - 1 is made up of small primes.
+    let proth = BigInt::from(165) * (BigInt::from(1) << 100) + 1;
+    assert_eq!(prove_or_probable(&proth), Primality::Prime);
+
+    // n = 2 q + 1 with q = 536871415 * 2**60 + 1, so proving n needs q proven first.
+    let q: BigInt = "618970599562206954693591041".parse().unwrap();
+    let n: BigInt = "1237941199124413909387182083".parse().unwrap();
+    assert_eq!(n, BigInt::from(&q * 2u32) + 1);
+    assert_eq!(prove_or_probable(&n), Primality::Prime);
+
+    // For 2**127 - 1, trial division leaves a large composite cofactor of n - 1, so no proof.
+    let mersenne = (BigInt::from(1) << 127) - 1;
+    assert_eq!(prove_or_probable(&mersenne), Primality::ProbablyPrime);
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn test_miller_rabin_parallel() {
+    let bases: Vec<BigInt> = FIRST_100_PRIMES.iter().take(12).map(|a| BigInt::from(*a)).collect();
+
+    // 25326001 = 2251 * 11251 fools the bases 2, 3 and 5, 7 is a witness.
+    let n = BigInt::from(25_326_001);
+    assert!(miller_rabin_parallel(&n, &bases[..3]));
+    assert!(!miller_rabin_parallel(&n, &bases[..4]));
+    // 3215031751 fools the bases up to 7, but not 11.
+    let n = BigInt::from(3_215_031_751u64);
+    assert!(miller_rabin_parallel(&n, &bases[..4]));
+    assert!(!miller_rabin_parallel(&n, &bases));
+
+    let mersenne = (BigInt::from(1) << 127) - 1;
+    assert!(miller_rabin_parallel(&mersenne, &bases));
+    for p in FIRST_100_PRIMES.iter().skip(12) {
+        assert!(miller_rabin_parallel(&BigInt::from(*p), &bases));
+    }
+
+    // Agrees with the bases tried one at a time.
+    for n in 2..2000u32 {
+        let n = BigInt::from(n);
+        let sequential = bases
+            .iter()
+            .filter(|a| **a < n)
+            .all(|a| miller_rabin_test(&n, a.clone()));
+        let usable: Vec<BigInt> = bases.iter().filter(|a| **a < n).cloned().collect();
+        assert_eq!(miller_rabin_parallel(&n, &usable), sequential, "n = {}", n);
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn run_utensil(args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_fermats_utensil"))
+        .args(args)
+        .output()
+        .expect("the binary should run");
+    assert!(output.status.success(), "{:?} failed: {:?}", args, output);
+    output
+}
+
+fn utensil_with_stdin(args: &[&str], stdin: impl AsRef<[u8]>) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fermats_utensil"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("the binary should run");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_ref())
+        .expect("stdin should be writable");
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?} failed: {:?}", args, output);
+    String::from_utf8(output.stdout).expect("stdout should be UTF-8")
+}
+
+fn utensil(args: &[&str]) -> String {
+    String::from_utf8(run_utensil(args).stdout).expect("stdout should be UTF-8")
+}
+
+#[test]
+fn test_carmichael() {
+    assert_eq!(utensil(&["carmichael", "561"]), "yes\n");
+    assert_eq!(utensil(&["carmichael", "15"]), "no\n");
+}
+
+#[test]
+fn test_time_flag() {
+    let output = run_utensil(&["--time", "ptest", "97"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), utensil(&["ptest", "97"]));
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.lines().any(|line| line.starts_with("Elapsed: ")), "{}", stderr);
+}
+
+#[test]
+fn test_numbers_from_stdin() {
+    assert_eq!(
+        utensil_with_stdin(&["ptest"], "97\n100\n561\n"),
+        "Probable prime\nComposite\nComposite\n"
+    );
+    assert_eq!(utensil_with_stdin(&["carmichael"], "15\n\n561\n"), "no\nyes\n");
+}
+
+#[test]
+fn test_invalid_utf8_on_stdin() {
+    assert_eq!(
+        utensil_with_stdin(&["ptest"], b"97\n\xff\xfe\n100\n"),
+        "Probable prime\nComposite\n"
+    );
+}
+
+#[test]
+fn test_sieve() {
+    let expected = "2\n3\n5\n7\n11\n13\n17\n19\n23\n29\n";
+    assert_eq!(utensil(&["sieve", "30"]), expected);
+    assert_eq!(utensil(&["sieve", "30", "--chunk", "1"]), expected);
+    assert_eq!(utensil(&["sieve", "30", "--chunk", "3"]), expected);
+
+    let output = utensil(&["sieve", "100000"]);
+    assert_eq!(output.lines().count(), 9592);
+    assert_eq!(output.lines().last(), Some("99991"));
+}
+
+#[test]
+fn test_ptest_small_factors() {
+    assert_eq!(utensil(&["ptest", "1000000"]), "Composite\n");
+    assert_eq!(utensil(&["ptest", "3*2^127-3"]), "Composite\n");
+    assert_eq!(
+        utensil_with_stdin(&["ptest"], "0\n1\n-5\n2\n541\n"),
+        "Composite\nComposite\nComposite\nProbable prime\nProbable prime\n"
+    );
+}
+
+#[test]
+fn test_number_expressions() {
+    assert_eq!(utensil(&["ptest", "2^31-1"]), "Probable prime\n");
+    assert_eq!(utensil(&["ptest", "2^32-1"]), "Composite\n");
+    assert_eq!(utensil(&["ptest", "10^18 + 9"]), "Probable prime\n");
+    assert_eq!(utensil(&["carmichael", "3*11*17"]), "yes\n");
+    assert_eq!(
+        utensil_with_stdin(&["ptest"], "2^61-1\n2^2^2+1\n4*5-1\n"),
+        "Probable prime\nProbable prime\nProbable prime\n"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fermats_utensil"))
+        .args(["ptest", "2^^3"])
+        .output()
+        .expect("the binary should run");
+    assert!(!output.status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fermats_utensil"))
+        .args(["ptest", "10^4000000000"])
+        .output()
+        .expect("the binary should run");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("has more than 67108864 bits"), "{}", stderr);
+}
+
+#[test]
+fn test_radix() {
+    assert_eq!(utensil(&["--radix", "16", "sieve", "20"]), "2\n3\n5\n7\nb\nd\n11\n13\n");
+    assert_eq!(utensil(&["--radix", "2", "sieve", "7"]), "10\n11\n101\n111\n");
+    assert_eq!(utensil(&["--radix", "10", "sieve", "7"]), utensil(&["sieve", "7"]));
+
+    for radix in ["1", "37", "hex"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_fermats_utensil"))
+            .args(["--radix", radix, "sieve", "7"])
+            .output()
+            .expect("the binary should run");
+        assert!(!output.status.success(), "radix {}", radix);
+    }
+}
+
+#[test]
+fn test_batch() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let input = dir.join("batch_input.txt");
+    let output = dir.join("batch_output.csv");
+    std::fs::write(&input, "97\n100\nabc\n\n561\n1\n2^61-1\n").unwrap();
+
+    let result = run_utensil(&[
+        "batch",
+        "--input",
+        input.to_str().unwrap(),
+        "--output",
+        output.to_str().unwrap(),
+    ]);
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    assert!(stderr.starts_with("Line 3: \"abc\""), "{}", stderr);
+
+    let csv = std::fs::read_to_string(&output).unwrap();
+    assert_eq!(
+        csv,
+        "number,result,method\n\
+         97,prime,Miller-Rabin base 41\n\
+         100,composite,Fermat base 2\n\
+         561,composite,Miller-Rabin base 2\n\
+         1,composite,none\n\
+         2305843009213693951,prime,Miller-Rabin base 41\n"
+    );
+}
+
+#[test]
+fn test_totient() {
+    assert_eq!(utensil(&["totient", "12"]), "4\n");
+    assert_eq!(utensil(&["totient", "97"]), "96\n");
+    assert_eq!(utensil(&["totient", "1"]), "1\n");
+    assert_eq!(utensil(&["totient", "2^61-1"]), "2305843009213693950\n");
+    assert_eq!(utensil(&["--radix", "16", "totient", "97"]), "60\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fermats_utensil"))
+        .args(["totient", "0"])
+        .output()
+        .expect("the binary should run");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_totient_from_stdin() {
+    assert_eq!(utensil_with_stdin(&["totient"], "12\n\n97\n2^10\n"), "4\n96\n512\n");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fermats_utensil"))
+        .arg("totient")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("the binary should run");
+    child.stdin.take().unwrap().write_all(b"12\n0\n97\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "4\n96\n");
+}
+
+#[test]
+fn test_factor() {
+    assert_eq!(utensil(&["factor", "1960"]), "2^3 * 5 * 7^2\n");
+    assert_eq!(utensil(&["factor", "97"]), "97\n");
+    assert_eq!(utensil(&["factor", "1"]), "1\n");
+    assert_eq!(utensil(&["factor", "2^64+1"]), "274177 * 67280421310721\n");
+    assert_eq!(utensil_with_stdin(&["factor"], "12\n561\n"), "2^2 * 3\n3 * 11 * 17\n");
+    assert_eq!(utensil(&["--radix", "16", "factor", "1960"]), "2^3 * 5 * 7^2\n");
+    assert_eq!(utensil(&["--radix", "16", "factor", "2^64+1"]), "42f01 * 3d30f19cd101\n");
+    assert_eq!(utensil(&["--radix", "2", "factor", "1"]), "1\n");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_fermats_utensil"))
+        .arg("factor")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("the binary should run");
+    child.stdin.take().unwrap().write_all(b"12\n0\n-4\n561\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "2^2 * 3\n3 * 11 * 17\n");
+}